@@ -0,0 +1,109 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use crate::{DiscoveredDevice, Result, Serial};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+/// A device recorded in the discovery cache.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CachedDevice {
+    /// Serial number of the device.
+    pub serial: Serial,
+    /// Address the device was last found at.
+    pub address: SocketAddr,
+    /// Label of the device, if known.
+    pub label: Option<String>,
+    /// Name of the product, if known.
+    pub product: Option<String>,
+    /// Time the device was first discovered (in seconds since the epoch).
+    pub first_seen: u64,
+    /// Time the device was last discovered (in seconds since the epoch).
+    pub last_seen: u64,
+}
+
+/// Devices found by previous discoveries, stored in the platform cache
+/// directory so that serials can be resolved without a broadcast.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct DiscoveryCache {
+    #[serde(default)]
+    devices: Vec<CachedDevice>,
+}
+
+impl DiscoveryCache {
+    /// Returns the location of the cache file.
+    pub fn path() -> Option<PathBuf> {
+        crate::project_dirs().map(|d| d.cache_dir().join("devices.toml"))
+    }
+
+    /// Loads the cache file.
+    ///
+    /// A missing or unreadable cache results in an empty cache.
+    pub async fn load() -> DiscoveryCache {
+        let cache = match Self::path() {
+            Some(p) => fs::read_to_string(p).await.ok(),
+            None => None,
+        };
+
+        cache
+            .and_then(|c| toml::from_str(&c).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the cache file, creating its directory if needed.
+    pub async fn save(&self) -> Result<()> {
+        match Self::path() {
+            Some(path) => crate::write_file(&path, &toml::to_string(self)?).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns every cached device.
+    pub fn devices(&self) -> &[CachedDevice] {
+        &self.devices
+    }
+
+    /// Returns the cached device with the given serial.
+    pub fn find(&self, serial: Serial) -> Option<&CachedDevice> {
+        self.devices.iter().find(|d| d.serial == serial)
+    }
+
+    /// Records a discovered device, returning its entry so that the label and
+    /// product can be filled in.
+    pub fn insert(&mut self, device: &DiscoveredDevice) -> &mut CachedDevice {
+        let now = crate::since_epoch().as_secs();
+        let index = match self.devices.iter().position(|d| d.serial == device.serial) {
+            Some(index) => index,
+            None => {
+                self.devices.push(CachedDevice {
+                    serial: device.serial,
+                    address: device.address,
+                    label: None,
+                    product: None,
+                    first_seen: now,
+                    last_seen: now,
+                });
+                self.devices.len() - 1
+            }
+        };
+
+        let entry = &mut self.devices[index];
+        entry.address = device.address;
+        entry.last_seen = now;
+        entry
+    }
+
+    /// Records the new label of a device, identified by its serial or, if that
+    /// is unknown, by its address.
+    pub fn set_label(&mut self, serial: Option<Serial>, address: SocketAddr, label: &str) {
+        for device in &mut self.devices {
+            if serial.map_or(device.address == address, |s| device.serial == s) {
+                device.label = Some(label.to_string());
+            }
+        }
+    }
+
+    /// Removes every device from the cache.
+    pub fn clear(&mut self) {
+        self.devices.clear();
+    }
+}
@@ -0,0 +1,28 @@
+/// A location or group that a device belongs to.
+///
+/// Devices are members of the same collection when they share its `id`; the
+/// label with the newest `updated_at` is the name of the collection.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Collection {
+    /// Identifier shared by every member of the collection.
+    pub id: [u8; 16],
+    /// Name of the collection.
+    pub label: String,
+    /// Time of the last change (in nanoseconds since the epoch).
+    pub updated_at: u64,
+}
+
+impl Collection {
+    /// Creates a new collection with a random ID.
+    pub fn new(label: &str) -> Collection {
+        let mut id = [0u8; 16];
+        id[..8].copy_from_slice(&crate::random_u64().to_le_bytes());
+        id[8..].copy_from_slice(&crate::random_u64().to_le_bytes());
+
+        Collection {
+            id,
+            label: label.to_string(),
+            updated_at: crate::since_epoch().as_nanos() as u64,
+        }
+    }
+}
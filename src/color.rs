@@ -0,0 +1,563 @@
+use crate::LifxError;
+use serde::{Deserialize, Serialize};
+
+/// A color in the hue, saturation, brightness and kelvin representation used
+/// by LIFX devices.
+///
+/// Components are stored in the raw protocol units, where hue, saturation and
+/// brightness each span the full range of a `u16`. These are also the units
+/// used when serialized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "RawHsbk")]
+pub struct Hsbk {
+    hue: u16,
+    saturation: u16,
+    brightness: u16,
+    kelvin: u16,
+}
+
+impl Hsbk {
+    /// Lowest color temperature supported by LIFX devices.
+    pub const MIN_KELVIN: u16 = 1500;
+
+    /// Highest color temperature supported by LIFX devices.
+    pub const MAX_KELVIN: u16 = 9000;
+
+    /// Creates a color from a hue (in degrees), saturation and brightness (in
+    /// percent) and color temperature (in kelvin).
+    ///
+    /// Hue wraps around at 360 degrees, while the other components are
+    /// clamped to their valid ranges.
+    pub fn new(hue: f32, saturation: f32, brightness: f32, kelvin: u16) -> Hsbk {
+        let mut color = Hsbk::default();
+        color.set_hue(hue);
+        color.set_saturation(saturation);
+        color.set_brightness(brightness);
+        color.set_kelvin(kelvin);
+        color
+    }
+
+    /// Creates a color from raw protocol values.
+    ///
+    /// The color temperature is clamped to the supported range.
+    pub fn from_raw(hue: u16, saturation: u16, brightness: u16, kelvin: u16) -> Hsbk {
+        Hsbk {
+            hue,
+            saturation,
+            brightness,
+            kelvin: kelvin.clamp(Self::MIN_KELVIN, Self::MAX_KELVIN),
+        }
+    }
+
+    /// Returns the raw protocol values of the hue, saturation, brightness and kelvin.
+    pub fn to_raw(self) -> (u16, u16, u16, u16) {
+        (self.hue, self.saturation, self.brightness, self.kelvin)
+    }
+
+    /// Returns the hue in degrees.
+    pub fn hue(&self) -> f32 {
+        360.0 * self.hue as f32 / 0x10000 as f32
+    }
+
+    /// Returns the saturation in percent.
+    pub fn saturation(&self) -> f32 {
+        percent_from_raw(self.saturation)
+    }
+
+    /// Returns the brightness in percent.
+    pub fn brightness(&self) -> f32 {
+        percent_from_raw(self.brightness)
+    }
+
+    /// Returns the color temperature in kelvin.
+    pub fn kelvin(&self) -> u16 {
+        self.kelvin
+    }
+
+    /// Sets the hue in degrees.
+    pub fn set_hue(&mut self, hue: f32) {
+        let turns = hue.rem_euclid(360.0) / 360.0;
+        self.hue = ((turns * 0x10000 as f32).round() as u32 % 0x10000) as u16;
+    }
+
+    /// Sets the saturation in percent.
+    pub fn set_saturation(&mut self, saturation: f32) {
+        self.saturation = percent_to_raw(saturation);
+    }
+
+    /// Sets the brightness in percent.
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = percent_to_raw(brightness);
+    }
+
+    /// Sets the color temperature in kelvin.
+    pub fn set_kelvin(&mut self, kelvin: u16) {
+        self.kelvin = kelvin.clamp(Self::MIN_KELVIN, Self::MAX_KELVIN);
+    }
+
+    /// Creates a color from red, green and blue components, with the given
+    /// color temperature.
+    pub fn from_rgb(red: u8, green: u8, blue: u8, kelvin: u16) -> Hsbk {
+        let (hue, saturation, brightness) = rgb_to_hsb(red, green, blue);
+        Hsbk::new(hue, saturation, brightness, kelvin)
+    }
+}
+
+impl Default for Hsbk {
+    /// Neutral white at zero brightness.
+    fn default() -> Hsbk {
+        Hsbk::from_raw(0, 0, 0, 3500)
+    }
+}
+
+/// Serialized form of [`Hsbk`], which is validated when deserializing.
+#[derive(Deserialize)]
+struct RawHsbk {
+    hue: u16,
+    saturation: u16,
+    brightness: u16,
+    kelvin: u16,
+}
+
+impl From<RawHsbk> for Hsbk {
+    fn from(raw: RawHsbk) -> Hsbk {
+        Hsbk::from_raw(raw.hue, raw.saturation, raw.brightness, raw.kelvin)
+    }
+}
+
+/// Converts red, green and blue components to a hue (in degrees), and
+/// saturation and brightness (in percent).
+fn rgb_to_hsb(red: u8, green: u8, blue: u8) -> (f32, f32, f32) {
+    let (r, g, b) = (
+        red as f32 / 255.0,
+        green as f32 / 255.0,
+        blue as f32 / 255.0,
+    );
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    (hue, saturation * 100.0, max * 100.0)
+}
+
+/// A color written as a CSS color name, `#rrggbb`, `rgb:R,G,B`, `hsb:H,S,B` or
+/// `kelvin:K`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpec {
+    /// A hue (in degrees), saturation and brightness (in percent).
+    Hsb(f32, f32, f32),
+    /// White at a color temperature (in kelvin).
+    Kelvin(u16),
+}
+
+impl std::str::FromStr for ColorSpec {
+    type Err = LifxError;
+
+    fn from_str(s: &str) -> crate::Result<ColorSpec> {
+        let invalid = || LifxError::InvalidColor(s.to_string());
+        let components =
+            |raw: &str| -> Vec<String> { raw.split(',').map(|c| c.trim().to_string()).collect() };
+
+        let rgb = if let Some(hex) = s.strip_prefix('#') {
+            // Parsing alone would also accept a sign
+            if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+
+            // Short codes such as #f80 repeat each digit
+            let hex: String = match hex.len() {
+                3 => hex.chars().flat_map(|c| [c, c]).collect(),
+                6 => hex.to_string(),
+                _ => return Err(invalid()),
+            };
+            let rgb = u32::from_str_radix(&hex, 16).map_err(|_| invalid())?;
+            rgb_from_u32(rgb)
+        } else if let Some(rgb) = s.strip_prefix("rgb:") {
+            match components(rgb)[..] {
+                [ref r, ref g, ref b] => [
+                    r.parse().map_err(|_| invalid())?,
+                    g.parse().map_err(|_| invalid())?,
+                    b.parse().map_err(|_| invalid())?,
+                ],
+                _ => return Err(invalid()),
+            }
+        } else if let Some(hsb) = s.strip_prefix("hsb:") {
+            let hsb: Vec<f32> = components(hsb)
+                .iter()
+                .map(|c| c.parse::<f32>().map_err(|_| invalid()))
+                .collect::<crate::Result<_>>()?;
+            return match hsb[..] {
+                [h, s, b]
+                    if (0.0..=360.0).contains(&h)
+                        && (0.0..=100.0).contains(&s)
+                        && (0.0..=100.0).contains(&b) =>
+                {
+                    Ok(ColorSpec::Hsb(h, s, b))
+                }
+                _ => Err(invalid()),
+            };
+        } else if let Some(kelvin) = s.strip_prefix("kelvin:") {
+            let kelvin = kelvin.trim().parse::<u16>().map_err(|_| invalid())?;
+            if !(Hsbk::MIN_KELVIN..=Hsbk::MAX_KELVIN).contains(&kelvin) {
+                return Err(invalid());
+            }
+            return Ok(ColorSpec::Kelvin(kelvin));
+        } else {
+            // Names are matched ignoring case and separators, e.g. "Light Blue"
+            let name: String = s
+                .chars()
+                .filter(|c| !matches!(c, ' ' | '-' | '_'))
+                .flat_map(char::to_lowercase)
+                .collect();
+            let rgb = CSS_COLORS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, rgb)| *rgb)
+                .ok_or_else(invalid)?;
+            rgb_from_u32(rgb)
+        };
+
+        let (hue, saturation, brightness) = rgb_to_hsb(rgb[0], rgb[1], rgb[2]);
+        Ok(ColorSpec::Hsb(hue, saturation, brightness))
+    }
+}
+
+fn rgb_from_u32(rgb: u32) -> [u8; 3] {
+    let [_, r, g, b] = rgb.to_be_bytes();
+    [r, g, b]
+}
+
+fn percent_from_raw(raw: u16) -> f32 {
+    100.0 * raw as f32 / u16::MAX as f32
+}
+
+fn percent_to_raw(percent: f32) -> u16 {
+    // NaN is treated as zero
+    let fraction = if percent.is_nan() {
+        0.0
+    } else {
+        percent.clamp(0.0, 100.0) / 100.0
+    };
+    (fraction * u16::MAX as f32).round() as u16
+}
+
+// Named colors from CSS Color Module Level 4
+static CSS_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_brightness_does_not_overflow() {
+        let color = Hsbk::new(0.0, 100.0, 100.0, 3500);
+        assert_eq!(color.to_raw(), (0, u16::MAX, u16::MAX, 3500));
+        assert_eq!(color.brightness(), 100.0);
+    }
+
+    #[test]
+    fn percentages_are_clamped() {
+        let color = Hsbk::new(0.0, -10.0, 150.0, 3500);
+        assert_eq!(color.saturation(), 0.0);
+        assert_eq!(color.brightness(), 100.0);
+
+        let color = Hsbk::new(0.0, f32::NAN, 50.0, 3500);
+        assert_eq!(color.saturation(), 0.0);
+    }
+
+    #[test]
+    fn hue_wraps() {
+        let mut color = Hsbk::default();
+        color.set_hue(360.0);
+        assert_eq!(color.to_raw().0, 0);
+
+        color.set_hue(-90.0);
+        assert_eq!(color.hue(), 270.0);
+
+        // Values just below 360 degrees round up to a full turn
+        color.set_hue(359.999);
+        assert_eq!(color.to_raw().0, 0);
+
+        color.set_hue(180.0);
+        assert_eq!(color.to_raw().0, 0x8000);
+    }
+
+    #[test]
+    fn kelvin_is_clamped() {
+        assert_eq!(Hsbk::new(0.0, 0.0, 0.0, 1000).kelvin(), Hsbk::MIN_KELVIN);
+        assert_eq!(Hsbk::new(0.0, 0.0, 0.0, 10000).kelvin(), Hsbk::MAX_KELVIN);
+        assert_eq!(Hsbk::from_raw(0, 0, 0, 0).kelvin(), Hsbk::MIN_KELVIN);
+        assert_eq!(Hsbk::default().kelvin(), 3500);
+
+        let color: Hsbk =
+            toml::from_str("hue = 0\nsaturation = 0\nbrightness = 0\nkelvin = 0").unwrap();
+        assert_eq!(color.kelvin(), Hsbk::MIN_KELVIN);
+    }
+
+    fn assert_hsb(actual: (f32, f32, f32), expected: (f32, f32, f32)) {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        assert!(
+            close(actual.0, expected.0)
+                && close(actual.1, expected.1)
+                && close(actual.2, expected.2),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn parse_hsb(spec: &str) -> (f32, f32, f32) {
+        match spec.parse::<ColorSpec>() {
+            Ok(ColorSpec::Hsb(h, s, b)) => (h, s, b),
+            other => panic!("{} parsed as {:?}", spec, other),
+        }
+    }
+
+    #[test]
+    fn rgb_conversion() {
+        assert_hsb(rgb_to_hsb(255, 0, 0), (0.0, 100.0, 100.0));
+        assert_hsb(rgb_to_hsb(0, 255, 0), (120.0, 100.0, 100.0));
+        assert_hsb(rgb_to_hsb(0, 0, 255), (240.0, 100.0, 100.0));
+        assert_hsb(rgb_to_hsb(255, 0, 255), (300.0, 100.0, 100.0));
+        assert_hsb(rgb_to_hsb(255, 136, 0), (32.0, 100.0, 100.0));
+        assert_hsb(rgb_to_hsb(128, 128, 128), (0.0, 0.0, 50.2));
+        assert_hsb(rgb_to_hsb(0, 0, 0), (0.0, 0.0, 0.0));
+        assert_hsb(rgb_to_hsb(255, 255, 255), (0.0, 0.0, 100.0));
+    }
+
+    #[test]
+    fn parse_hex() {
+        assert_hsb(parse_hsb("#ff8800"), (32.0, 100.0, 100.0));
+        assert_hsb(parse_hsb("#FF8800"), (32.0, 100.0, 100.0));
+        assert_hsb(parse_hsb("#f80"), (32.0, 100.0, 100.0));
+
+        for spec in [
+            "#ff880", "#ff88000", "#", "#gg8800", "#+fffff", "#-ff", "ff8800",
+        ] {
+            assert!(spec.parse::<ColorSpec>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn parse_rgb() {
+        assert_hsb(parse_hsb("rgb:255,136,0"), (32.0, 100.0, 100.0));
+        assert_hsb(parse_hsb("rgb:0, 0, 255"), (240.0, 100.0, 100.0));
+
+        for spec in [
+            "rgb:256,0,0",
+            "rgb:255,0",
+            "rgb:255,0,0,0",
+            "rgb:-1,0,0",
+            "rgb:",
+        ] {
+            assert!(spec.parse::<ColorSpec>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn parse_hsb_ranges() {
+        assert_hsb(parse_hsb("hsb:30,100,80"), (30.0, 100.0, 80.0));
+        assert_hsb(parse_hsb("hsb:360,0,0"), (360.0, 0.0, 0.0));
+
+        for spec in [
+            "hsb:361,0,0",
+            "hsb:-1,0,0",
+            "hsb:0,101,0",
+            "hsb:0,0,-1",
+            "hsb:0,0",
+        ] {
+            assert!(spec.parse::<ColorSpec>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn parse_kelvin() {
+        assert_eq!(
+            "kelvin:2700".parse::<ColorSpec>().unwrap(),
+            ColorSpec::Kelvin(2700)
+        );
+        assert_eq!(
+            "kelvin:1500".parse::<ColorSpec>().unwrap(),
+            ColorSpec::Kelvin(1500)
+        );
+        assert_eq!(
+            "kelvin:9000".parse::<ColorSpec>().unwrap(),
+            ColorSpec::Kelvin(9000)
+        );
+
+        for spec in ["kelvin:1499", "kelvin:9001", "kelvin:", "kelvin:warm"] {
+            assert!(spec.parse::<ColorSpec>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn parse_names() {
+        assert_hsb(parse_hsb("red"), (0.0, 100.0, 100.0));
+        assert_hsb(parse_hsb("Orange"), parse_hsb("#ffa500"));
+        assert_hsb(parse_hsb("Light Blue"), parse_hsb("#add8e6"));
+        assert_hsb(parse_hsb("light-blue"), parse_hsb("lightblue"));
+        assert_hsb(parse_hsb("REBECCA_PURPLE"), parse_hsb("#663399"));
+
+        assert!("bogus".parse::<ColorSpec>().is_err());
+        assert!("".parse::<ColorSpec>().is_err());
+    }
+}
@@ -1,90 +1,657 @@
-use std::{collections::HashSet, net::SocketAddr};
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{anyhow, Result};
-use serde::{Deserialize, Deserializer};
+use crate::{LifxError, Result, Serial};
+use serde::{Deserialize, Deserializer, Serialize};
 use tokio::fs;
+use toml_edit::{value, ArrayOfTables, DocumentMut, Item, Table};
 
-fn deserialize_address<'de, D>(de: D) -> Result<SocketAddr, D::Error>
+fn deserialize_address<'de, D>(de: D) -> std::result::Result<Option<SocketAddr>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let raw: String = Deserialize::deserialize(de)?;
-    crate::parse_address(&raw)
-        .ok_or_else(|| serde::de::Error::custom(anyhow!("Invalid IP address.")))
+    let raw: Option<String> = Deserialize::deserialize(de)?;
+    raw.map(|raw| {
+        crate::parse_address(&raw).ok_or_else(|| serde::de::Error::custom("Invalid IP address."))
+    })
+    .transpose()
 }
 
-#[derive(Deserialize, Debug)]
+/// A named device entry from the configuration file.
+///
+/// Devices are identified by an address, a serial or both. Devices with only
+/// a serial are located through discovery.
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Device {
-    alias: String,
+    /// Name to refer to the device by.
+    pub alias: String,
 
-    #[serde(deserialize_with = "deserialize_address")]
-    address: SocketAddr,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_address",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// Address of the device.
+    pub address: Option<SocketAddr>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Serial of the device.
+    pub serial: Option<Serial>,
+}
+
+impl Device {
+    /// Parses a device address, with the port defaulting to
+    /// [`LIFX_PORT`](crate::LIFX_PORT) if not given.
+    pub fn parse_address(raw: &str) -> Option<SocketAddr> {
+        crate::parse_address(raw)
+    }
+}
+
+/// A named set of devices from the configuration file.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeviceGroup {
+    /// Name to refer to the group by.
+    pub name: String,
+    /// Aliases or addresses of the devices in the group.
+    pub members: Vec<String>,
+}
+
+/// A named set of device states that can be applied at once.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Scene {
+    /// Name to refer to the scene by.
+    pub name: String,
+    /// Duration (in milliseconds) of the transition to the scene.
+    #[serde(default)]
+    pub duration: u32,
+    /// States to set, where later entries override earlier ones.
+    pub devices: Vec<SceneState>,
+}
+
+/// State of a device or group in a scene.
+///
+/// Color components that are not given are left unchanged.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SceneState {
+    /// Address, serial, alias or group of the devices.
+    pub device: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Whether the device is turned on.
+    pub power: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Hue (in degrees).
+    pub hue: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Saturation (in percent).
+    pub saturation: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Brightness (in percent).
+    pub brightness: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Color temperature (in kelvin).
+    pub kelvin: Option<u16>,
 }
 
 #[derive(Deserialize, Default)]
 struct ConfigInner {
     default_device: Option<String>,
+    timeout: Option<u64>,
+    retries: Option<u32>,
+    discovery_timeout: Option<u64>,
+    #[serde(default)]
     devices: Vec<Device>,
+    #[serde(default)]
+    groups: Vec<DeviceGroup>,
+    #[serde(default)]
+    scenes: Vec<Scene>,
 }
 
-#[derive(Default, Debug)]
+/// Validated lifxc configuration.
+#[derive(Serialize, Default, Debug)]
 pub struct Config {
-    pub default_device: Option<SocketAddr>,
+    /// Address, serial, alias or group of the devices to control by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_device: Option<String>,
+    /// Timeout (in milliseconds) for each request attempt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Number of times to retry a request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Time (in milliseconds) to wait for devices to respond to discovery.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discovery_timeout: Option<u64>,
+    // Empty arrays would be written as values after the device tables, which
+    // TOML doesn't allow
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// Named devices.
     pub devices: Vec<Device>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// Named groups of devices.
+    pub groups: Vec<DeviceGroup>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// Named scenes.
+    pub scenes: Vec<Scene>,
 }
 
 impl Config {
-    pub async fn load() -> Result<Config> {
-        let proj_dirs = directories::ProjectDirs::from("com", "psr31", "lifxc");
-        let config_path = proj_dirs
-            .as_ref()
-            .map(|d| d.config_dir().join("config.toml"));
+    /// Returns the location of the configuration file.
+    pub fn path() -> Option<PathBuf> {
+        crate::project_dirs().map(|d| d.config_dir().join("config.toml"))
+    }
 
-        let config: ConfigInner = match config_path {
+    /// Loads the configuration file from the platform config directory.
+    ///
+    /// A missing file results in an empty configuration.
+    pub async fn load() -> Result<Config> {
+        match Self::path() {
             Some(ref p) => match fs::read_to_string(p).await {
-                Ok(config) => Some(toml::from_str(&config)?),
-                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
-                Err(e) => return Err(e.into()),
+                Ok(config) => Self::parse(&config),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+                Err(e) => Err(e.into()),
             },
-            None => None,
+            None => Ok(Config::default()),
         }
-        .unwrap_or_default();
+    }
+
+    /// Parses and validates the contents of a configuration file.
+    pub fn parse(raw: &str) -> Result<Config> {
+        let config: ConfigInner = toml::from_str(raw)?;
 
         // Check each alias is only used once
         let mut seen = HashSet::new();
         for device in &config.devices {
             if !seen.insert(device.alias.as_str()) {
-                return Err(anyhow!(
+                return Err(LifxError::InvalidConfig(format!(
                     "Device alias '{}' is used multiple times.",
                     device.alias
-                ));
+                )));
+            }
+
+            if device.address.is_none() && device.serial.is_none() {
+                return Err(LifxError::InvalidConfig(format!(
+                    "Device '{}' has neither an address or serial.",
+                    device.alias
+                )));
             }
         }
 
-        let default_device = config
-            .default_device
-            .as_deref()
-            .map(|dstr| {
-                if let Some(device) = config.devices.iter().find(|d| d.alias == dstr) {
-                    Ok(device.address)
-                } else {
-                    crate::parse_address(dstr).ok_or_else(|| {
-                        anyhow!("Default device is neither a valid IP address or alias.")
-                    })
+        // Groups share a namespace with aliases, and may only contain known devices
+        for group in &config.groups {
+            if !seen.insert(group.name.as_str()) {
+                return Err(LifxError::InvalidConfig(format!(
+                    "Group name '{}' is used multiple times.",
+                    group.name
+                )));
+            }
+
+            for member in &group.members {
+                if !is_device(&config.devices, member) {
+                    return Err(LifxError::InvalidConfig(format!(
+                        "Group '{}' member '{}' is neither a valid IP address, serial or alias.",
+                        group.name, member
+                    )));
                 }
-            })
-            .transpose()?;
+            }
+        }
+
+        // Scenes have their own namespace, and may contain devices or groups
+        let mut scenes = HashSet::new();
+        for scene in &config.scenes {
+            if !scenes.insert(scene.name.as_str()) {
+                return Err(LifxError::InvalidConfig(format!(
+                    "Scene name '{}' is used multiple times.",
+                    scene.name
+                )));
+            }
+
+            for state in &scene.devices {
+                if !is_device(&config.devices, &state.device)
+                    && !config.groups.iter().any(|g| g.name == state.device)
+                {
+                    return Err(LifxError::InvalidConfig(format!(
+                        "Scene '{}' device '{}' is neither a valid IP address, serial, alias or group.",
+                        scene.name, state.device
+                    )));
+                }
+            }
+        }
+
+        if let Some(ref default_device) = config.default_device {
+            if !is_device(&config.devices, default_device)
+                && !config.groups.iter().any(|g| &g.name == default_device)
+            {
+                return Err(LifxError::InvalidConfig(
+                    "Default device is neither a valid IP address, serial, alias or group."
+                        .to_string(),
+                ));
+            }
+        }
 
         Ok(Config {
-            default_device,
+            default_device: config.default_device,
+            timeout: config.timeout,
+            retries: config.retries,
+            discovery_timeout: config.discovery_timeout,
             devices: config.devices,
+            groups: config.groups,
+            scenes: config.scenes,
+        })
+    }
+
+    /// Returns the device with the given alias.
+    pub fn find_alias(&self, alias: &str) -> Option<&Device> {
+        self.devices.iter().find(|d| d.alias == alias)
+    }
+
+    /// Returns the group with the given name.
+    pub fn find_group(&self, name: &str) -> Option<&DeviceGroup> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    /// Returns the scene with the given name.
+    pub fn find_scene(&self, name: &str) -> Option<&Scene> {
+        self.scenes.iter().find(|s| s.name == name)
+    }
+}
+
+/// Checks whether `device` is an alias, address or serial.
+fn is_device(devices: &[Device], device: &str) -> bool {
+    devices.iter().any(|d| d.alias == device)
+        || crate::parse_address(device).is_some()
+        || device.parse::<Serial>().is_ok()
+}
+
+/// Derives a device alias from its label, e.g. "Desk Lamp" becomes "desk-lamp".
+fn alias_from_label(label: &str) -> String {
+    let mut alias = String::new();
+    for c in label.chars() {
+        if c.is_alphanumeric() {
+            alias.extend(c.to_lowercase());
+        } else if !alias.is_empty() && !alias.ends_with('-') {
+            alias.push('-');
+        }
+    }
+    alias.trim_end_matches('-').to_string()
+}
+
+/// The configuration file as a TOML document, which can be edited while
+/// preserving its comments and formatting.
+pub struct ConfigDocument {
+    path: PathBuf,
+    doc: DocumentMut,
+}
+
+impl ConfigDocument {
+    /// Loads the configuration file, or an empty document if it doesn't exist.
+    pub async fn load() -> Result<ConfigDocument> {
+        let path = Config::path().ok_or_else(|| {
+            LifxError::InvalidConfig("Unable to locate the configuration directory.".to_string())
+        })?;
+        let raw = match fs::read_to_string(&path).await {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(ConfigDocument {
+            path,
+            doc: raw.parse()?,
         })
     }
 
-    pub fn find_alias(&self, alias: &str) -> Option<SocketAddr> {
-        self.devices
+    /// Returns the location the document is saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Validates the document and writes it to the configuration file.
+    pub async fn save(&self) -> Result<()> {
+        let raw = self.doc.to_string();
+        Config::parse(&raw)?;
+        crate::write_file(&self.path, &raw).await
+    }
+
+    /// Adds a device with the given alias.
+    pub fn add_device(
+        &mut self,
+        alias: &str,
+        address: Option<SocketAddr>,
+        serial: Option<Serial>,
+    ) -> Result<()> {
+        if self.names().iter().any(|n| n == alias) {
+            return Err(LifxError::InvalidConfig(format!(
+                "Device alias '{}' is used multiple times.",
+                alias
+            )));
+        }
+
+        let mut device = Table::new();
+        device["alias"] = value(alias);
+        if let Some(address) = address {
+            device["address"] = value(address.to_string());
+        }
+        if let Some(serial) = serial {
+            device["serial"] = value(serial.to_string());
+        }
+        self.devices()?.push(device);
+        Ok(())
+    }
+
+    /// Removes the device with the given alias.
+    pub fn remove_device(&mut self, alias: &str) -> Result<()> {
+        let devices = self.devices()?;
+        let index = devices
+            .iter()
+            .position(|d| d.get("alias").and_then(Item::as_str) == Some(alias))
+            .ok_or_else(|| {
+                LifxError::InvalidConfig(format!("No device has the alias '{}'.", alias))
+            })?;
+        devices.remove(index);
+        Ok(())
+    }
+
+    /// Sets the address, serial or alias of the device to control by default.
+    pub fn set_default_device(&mut self, device: &str) {
+        self.doc["default_device"] = value(device);
+    }
+
+    /// Adds a scene, replacing any existing scene with the same name.
+    pub fn set_scene(&mut self, scene: &Scene) -> Result<()> {
+        let mut devices = ArrayOfTables::new();
+        for state in &scene.devices {
+            let mut device = Table::new();
+            device["device"] = value(state.device.as_str());
+            if let Some(power) = state.power {
+                device["power"] = value(power);
+            }
+            let components = [
+                ("hue", state.hue),
+                ("saturation", state.saturation),
+                ("brightness", state.brightness),
+            ];
+            for (key, component) in components {
+                if let Some(component) = component {
+                    // Rounded to avoid float noise such as 29.999998
+                    device[key] = value((f64::from(component) * 10.0).round() / 10.0);
+                }
+            }
+            if let Some(kelvin) = state.kelvin {
+                device["kelvin"] = value(i64::from(kelvin));
+            }
+            devices.push(device);
+        }
+
+        let mut table = Table::new();
+        table["name"] = value(scene.name.as_str());
+        if scene.duration > 0 {
+            table["duration"] = value(i64::from(scene.duration));
+        }
+        table["devices"] = Item::ArrayOfTables(devices);
+
+        let scenes = self
+            .doc
+            .entry("scenes")
+            .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()
+            .ok_or_else(|| {
+                LifxError::InvalidConfig("Scenes must be an array of tables.".to_string())
+            })?;
+        let existing = scenes
             .iter()
-            .find(|d| d.alias == alias)
-            .map(|d| d.address)
+            .position(|s| s.get("name").and_then(Item::as_str) == Some(&scene.name));
+        match existing {
+            Some(index) => *scenes.get_mut(index).unwrap() = table,
+            None => scenes.push(table),
+        }
+        Ok(())
+    }
+
+    /// Adds a discovered device by serial, with an alias derived from its
+    /// label.
+    ///
+    /// The address is only used to detect devices that are already configured.
+    /// It isn't written, as addresses are usually assigned by DHCP and may
+    /// change, whereas a device given by serial is found through the discovery
+    /// cache, which is updated whenever the device moves.
+    ///
+    /// Returns the alias given to the device, or `None` if a device with the
+    /// same serial or address is already configured.
+    pub fn import_device(
+        &mut self,
+        serial: Serial,
+        address: SocketAddr,
+        label: Option<&str>,
+    ) -> Result<Option<String>> {
+        let configured = self.devices()?.iter().any(|d| {
+            let field = |key| d.get(key).and_then(Item::as_str);
+            field("serial").and_then(|s| s.parse::<Serial>().ok()) == Some(serial)
+                || field("address").and_then(crate::parse_address) == Some(address)
+        });
+        if configured {
+            return Ok(None);
+        }
+
+        // Aliases must be unique, so a number is added to repeated labels
+        let base = label
+            .map(alias_from_label)
+            .filter(|a| !a.is_empty())
+            .unwrap_or_else(|| serial.to_string());
+        let names = self.names();
+        let mut alias = base.clone();
+        for i in 2.. {
+            if !names.contains(&alias) {
+                break;
+            }
+            alias = format!("{}-{}", base, i);
+        }
+
+        self.add_device(&alias, None, Some(serial))?;
+        Ok(Some(alias))
+    }
+
+    fn devices(&mut self) -> Result<&mut ArrayOfTables> {
+        let devices = self
+            .doc
+            .entry("devices")
+            .or_insert(Item::ArrayOfTables(ArrayOfTables::new()));
+
+        // An empty inline array can't hold tables, so is replaced
+        if devices.as_array().is_some_and(|a| a.is_empty()) {
+            *devices = Item::ArrayOfTables(ArrayOfTables::new());
+        }
+        devices.as_array_of_tables_mut().ok_or_else(|| {
+            LifxError::InvalidConfig("Devices must be an array of tables.".to_string())
+        })
+    }
+
+    /// Returns every device alias and group name.
+    fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for (key, field) in [("devices", "alias"), ("groups", "name")] {
+            if let Some(tables) = self.doc.get(key).and_then(Item::as_array_of_tables) {
+                names.extend(
+                    tables
+                        .iter()
+                        .filter_map(|t| t.get(field).and_then(Item::as_str))
+                        .map(str::to_string),
+                );
+            }
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+default_device = "downstairs"
+
+[[devices]]
+alias = "office"
+address = "192.168.0.4"
+
+[[devices]]
+alias = "kitchen"
+serial = "d073d5123456"
+
+[[groups]]
+name = "downstairs"
+members = ["kitchen", "192.168.0.5"]
+
+[[scenes]]
+name = "evening"
+
+[[scenes.devices]]
+device = "downstairs"
+brightness = 40
+"#;
+
+    fn document(raw: &str) -> ConfigDocument {
+        ConfigDocument {
+            path: PathBuf::new(),
+            doc: raw.parse().unwrap(),
+        }
+    }
+
+    fn parse_error(raw: &str) -> String {
+        match Config::parse(raw) {
+            Err(LifxError::InvalidConfig(message)) => message,
+            other => panic!("expected an invalid config, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert_eq!(config.default_device.as_deref(), Some("downstairs"));
+
+        let office = config.find_alias("office").unwrap();
+        assert_eq!(office.address, Some("192.168.0.4:56700".parse().unwrap()));
+        assert_eq!(office.serial, None);
+        let kitchen = config.find_alias("kitchen").unwrap();
+        assert_eq!(kitchen.serial, Some("d073d5123456".parse().unwrap()));
+
+        assert_eq!(config.find_group("downstairs").unwrap().members.len(), 2);
+        assert_eq!(config.find_scene("evening").unwrap().devices.len(), 1);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_references() {
+        let duplicate_alias = "[[devices]]\nalias = \"a\"\naddress = \"10.0.0.1\"\n\
+                               [[devices]]\nalias = \"a\"\naddress = \"10.0.0.2\"\n";
+        assert!(parse_error(duplicate_alias).contains("'a' is used multiple times"));
+
+        let no_address = "[[devices]]\nalias = \"a\"\n";
+        assert!(parse_error(no_address).contains("neither an address or serial"));
+
+        let group_alias = "[[devices]]\nalias = \"a\"\naddress = \"10.0.0.1\"\n\
+                           [[groups]]\nname = \"a\"\nmembers = []\n";
+        assert!(parse_error(group_alias).contains("Group name 'a'"));
+
+        let group_member = "[[groups]]\nname = \"g\"\nmembers = [\"missing\"]\n";
+        assert!(parse_error(group_member).contains("member 'missing'"));
+
+        let duplicate_scene = "[[scenes]]\nname = \"s\"\ndevices = []\n\
+                               [[scenes]]\nname = \"s\"\ndevices = []\n";
+        assert!(parse_error(duplicate_scene).contains("Scene name 's'"));
+
+        let scene_device = "[[scenes]]\nname = \"s\"\n\
+                            [[scenes.devices]]\ndevice = \"missing\"\n";
+        assert!(parse_error(scene_device).contains("device 'missing'"));
+
+        assert!(parse_error("default_device = \"missing\"\n").contains("Default device"));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_values() {
+        let address = "[[devices]]\nalias = \"a\"\naddress = \"not an address\"\n";
+        assert!(matches!(Config::parse(address), Err(LifxError::Toml(_))));
+
+        let serial = "[[devices]]\nalias = \"a\"\nserial = \"d073d5\"\n";
+        assert!(matches!(Config::parse(serial), Err(LifxError::Toml(_))));
+    }
+
+    #[test]
+    fn edit_devices() {
+        let mut doc = document(&format!("# Lights\n{}", CONFIG));
+        let serial: Serial = "d073d5aabbcc".parse().unwrap();
+        doc.add_device("desk", None, Some(serial)).unwrap();
+        doc.add_device("porch", Some("10.0.0.9:56700".parse().unwrap()), None)
+            .unwrap();
+        doc.remove_device("office").unwrap();
+        doc.set_default_device("desk");
+
+        // Aliases can't be reused, and only existing devices can be removed
+        assert!(doc.add_device("downstairs", None, Some(serial)).is_err());
+        assert!(doc.remove_device("office").is_err());
+
+        let raw = doc.doc.to_string();
+        assert!(raw.starts_with("# Lights\n"));
+        let config = Config::parse(&raw).unwrap();
+        let aliases: Vec<&str> = config.devices.iter().map(|d| d.alias.as_str()).collect();
+        assert_eq!(aliases, ["kitchen", "desk", "porch"]);
+        assert_eq!(config.find_alias("desk").unwrap().serial, Some(serial));
+        assert_eq!(config.default_device.as_deref(), Some("desk"));
+    }
+
+    #[test]
+    fn import_devices() {
+        let mut doc = document(CONFIG);
+        let address = "10.0.0.9:56700".parse().unwrap();
+        let known: Serial = "d073d5123456".parse().unwrap();
+        assert_eq!(
+            doc.import_device(known, address, Some("Kitchen")).unwrap(),
+            None
+        );
+
+        // Repeated labels are given numbered aliases
+        let desk = Some("Desk Lamp");
+        let first = "d073d5000001".parse().unwrap();
+        let second = "d073d5000002".parse().unwrap();
+        let alias = doc.import_device(first, address, desk).unwrap();
+        assert_eq!(alias.as_deref(), Some("desk-lamp"));
+        let address = "10.0.0.10:56700".parse().unwrap();
+        let alias = doc.import_device(second, address, desk).unwrap();
+        assert_eq!(alias.as_deref(), Some("desk-lamp-2"));
+
+        let config = Config::parse(&doc.doc.to_string()).unwrap();
+        assert_eq!(
+            config.find_alias("desk-lamp-2").unwrap().serial,
+            Some(second)
+        );
+    }
+
+    #[tokio::test]
+    async fn save_replaces_file() {
+        let dir = std::env::temp_dir().join(format!("lifxc-test-{:016x}", crate::random_u64()));
+        let mut doc = document(CONFIG);
+        doc.path = dir.join("config.toml");
+        crate::write_file(&doc.path, "# Old\n").await.unwrap();
+
+        doc.set_default_device("office");
+        doc.save().await.unwrap();
+        let saved = fs::read_to_string(&doc.path).await.unwrap();
+        assert_eq!(saved, doc.doc.to_string());
+
+        // Invalid documents aren't written
+        doc.set_default_device("missing");
+        assert!(doc.save().await.is_err());
+        assert_eq!(fs::read_to_string(&doc.path).await.unwrap(), saved);
+
+        // No temporary files are left behind
+        let mut entries = fs::read_dir(&dir).await.unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name());
+        }
+        assert_eq!(names, ["config.toml"]);
+
+        fs::remove_dir_all(&dir).await.unwrap();
     }
 }
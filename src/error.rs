@@ -0,0 +1,65 @@
+use thiserror::Error;
+
+/// Errors returned by lifxc.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum LifxError {
+    /// A packet could not be decoded.
+    #[error("Bad packet received from device.")]
+    MalformedPacket,
+
+    /// The device replied with a message other than the one expected.
+    #[error("Unexpected packet received from device.")]
+    UnexpectedPacket,
+
+    /// The device replied StateUnhandled to a message of the given type.
+    #[error("Device does not support this operation.")]
+    Unsupported(u16),
+
+    /// The product does not have a capability required by the operation.
+    #[error("{product} does not support {capability}.")]
+    NotCapable {
+        /// Name of the product.
+        product: &'static str,
+        /// Description of the missing capability.
+        capability: &'static str,
+    },
+
+    /// The device did not respond to any attempt of a request.
+    #[error("Device did not respond after {attempts} attempts.")]
+    NoResponse {
+        /// Number of times the request was sent.
+        attempts: u32,
+    },
+
+    /// A serial number could not be parsed.
+    #[error("Invalid serial number '{0}'.")]
+    InvalidSerial(String),
+
+    /// A color could not be parsed.
+    #[error("Invalid color '{0}'.")]
+    InvalidColor(String),
+
+    /// The configuration file is invalid.
+    #[error("{0}")]
+    InvalidConfig(String),
+
+    /// The configuration file is not valid TOML.
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+
+    /// A file could not be written as TOML.
+    #[error(transparent)]
+    TomlSerialize(#[from] toml::ser::Error),
+
+    /// The configuration file could not be parsed for editing.
+    #[error(transparent)]
+    TomlEdit(#[from] toml_edit::TomlError),
+
+    /// Reading or writing a file, or a socket operation, failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Result type used throughout lifxc.
+pub type Result<T> = std::result::Result<T, LifxError>;
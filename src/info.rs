@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+/// Firmware running on the device's host microcontroller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HostFirmware {
+    /// Build time (in nanoseconds since the epoch).
+    pub build: u64,
+    /// Major version number.
+    pub version_major: u16,
+    /// Minor version number.
+    pub version_minor: u16,
+}
+
+/// Wi-Fi status of the device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WifiInfo {
+    /// Raw signal strength reported by the device.
+    pub signal: f32,
+}
+
+impl WifiInfo {
+    /// Returns the received signal strength (in dBm), or `None` if the device
+    /// reported no signal.
+    pub fn rssi(&self) -> Option<i32> {
+        // The logarithm of a zero or negative signal has no meaningful value
+        if self.signal > 0.0 && self.signal.is_finite() {
+            Some((10.0 * self.signal.log10() + 0.5).floor() as i32)
+        } else {
+            None
+        }
+    }
+}
+
+/// Hardware vendor and product of the device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Version {
+    /// Vendor ID, which is [`LIFX_VENDOR`](crate::LIFX_VENDOR) for LIFX devices.
+    pub vendor: u32,
+    /// Product ID within the vendor.
+    pub product: u32,
+}
+
+/// Runtime information of the device, all in nanoseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// Current time on the device (since the epoch).
+    pub time: u64,
+    /// Time since the device was last powered on.
+    pub uptime: u64,
+    /// Duration the device was last powered off for.
+    pub downtime: u64,
+}
+
+impl DeviceInfo {
+    /// Returns the time since the device was last powered on.
+    pub fn uptime(&self) -> Duration {
+        Duration::from_nanos(self.uptime)
+    }
+
+    /// Returns the duration the device was last powered off for.
+    pub fn downtime(&self) -> Duration {
+        Duration::from_nanos(self.downtime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rssi_in_dbm() {
+        assert_eq!(WifiInfo { signal: 1e-5 }.rssi(), Some(-50));
+        assert_eq!(WifiInfo { signal: 3e-7 }.rssi(), Some(-65));
+    }
+
+    #[test]
+    fn rssi_without_signal() {
+        assert_eq!(WifiInfo { signal: 0.0 }.rssi(), None);
+        assert_eq!(WifiInfo { signal: -1.0 }.rssi(), None);
+        assert_eq!(WifiInfo { signal: f32::NAN }.rssi(), None);
+    }
+}
@@ -0,0 +1,38 @@
+//! Library for controlling LIFX smart lights over the LAN protocol.
+//!
+//! The [`LightConnection`] type talks to a single device, while
+//! [`LightConnection::device_stream`] discovers devices on the local network.
+//! [`Config`] loads the lifxc configuration file used by the command line
+//! utility, and [`DiscoveryCache`] remembers previously discovered devices.
+
+#![warn(missing_docs)]
+
+mod cache;
+mod collection;
+mod color;
+mod config;
+mod error;
+mod info;
+mod light;
+mod packet;
+mod products;
+mod snapshot;
+mod util;
+mod waveform;
+
+pub use cache::{CachedDevice, DiscoveryCache};
+pub use collection::Collection;
+pub use color::{ColorSpec, Hsbk};
+pub use config::{Config, ConfigDocument, Device, DeviceGroup, Scene, SceneState};
+pub use error::{LifxError, Result};
+pub use info::{DeviceInfo, HostFirmware, Version, WifiInfo};
+pub use light::{DeviceStream, DiscoveredDevice, LightConnection, LightState};
+pub use packet::{Message, Response, Serial};
+pub use products::{Features, Product, Upgrade, LIFX_VENDOR};
+pub use snapshot::{DeviceSnapshot, NamedSnapshot, SnapshotStore, StateSnapshot};
+pub use waveform::{HsbkMask, Waveform, WaveformEffect};
+
+pub(crate) use util::*;
+
+/// Default UDP port used by LIFX devices.
+pub const LIFX_PORT: u16 = 56700;
@@ -1,146 +1,667 @@
-use crate::{Message, Response};
-use anyhow::{anyhow, Result};
-use futures::Stream;
+use crate::{
+    timeout, Collection, DeviceInfo, Features, HostFirmware, Hsbk, HsbkMask, LifxError, Message,
+    Product, Response, Result, Serial, Timeout, Version, WaveformEffect, WifiInfo,
+};
+use futures::{Stream, StreamExt};
 use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::net::SocketAddr;
+use std::sync::OnceLock;
 use std::task::Poll;
 use tokio::net::UdpSocket;
 
-const UNEXPECTED_PACKET: &str = "Unexpected packet received from device.";
+/// Default timeout (in milliseconds) for each request attempt.
+pub(crate) const DEFAULT_TIMEOUT: u64 = 500;
 
+/// Default number of times a request is retried.
+pub(crate) const DEFAULT_RETRIES: u32 = 3;
+
+/// Color, power and label of a light.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightState {
+    /// Current color.
+    pub color: Hsbk,
+    /// Whether the light is turned on.
+    pub power: bool,
+    /// Label of the device.
+    pub label: String,
+}
+
+/// Connection to a single LIFX device.
 pub struct LightConnection {
     sock: UdpSocket,
     addr: SocketAddr,
+    serial: Option<Serial>,
+    source: u32,
     sequence: u8,
+    timeout: u64,
+    retries: u32,
+    product: Option<Option<&'static Product>>,
+    features: Option<Option<Features>>,
 }
 
 impl LightConnection {
+    /// Creates a connection to the device at `addr`.
     pub async fn new(addr: SocketAddr) -> Result<LightConnection> {
         Ok(LightConnection {
             sock: UdpSocket::bind("0.0.0.0:0").await?,
             addr,
+            serial: None,
+            source: process_source(),
             sequence: 0,
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            product: None,
+            features: None,
         })
     }
 
+    /// Addresses packets to the device with the given serial, so that they are
+    /// ignored by any other device at the same address.
+    pub fn set_serial(&mut self, serial: Serial) {
+        self.serial = Some(serial);
+    }
+
+    /// Sets the timeout (in milliseconds) for each request attempt.
+    pub fn set_timeout(&mut self, ms: u64) {
+        self.timeout = ms;
+    }
+
+    /// Sets the number of times a request is retried after timing out.
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    /// Broadcasts a discovery request and returns a stream of responding devices.
     pub async fn device_stream() -> Result<DeviceStream> {
         let sock = UdpSocket::bind("0.0.0.0:0").await?;
         sock.set_broadcast(true)?;
 
         // 0x02 - GetService
+        let source = process_source();
         sock.send_to(
-            &Message::GetService.encode(false, 0, None),
+            &Message::GetService.encode(source, false, 0, None),
             SocketAddr::from(([255, 255, 255, 255], crate::LIFX_PORT)),
         )
         .await?;
 
         Ok(DeviceStream {
             sock,
+            source,
             seen: HashSet::new(),
         })
     }
 
-    pub async fn get_power(&mut self) -> Result<bool> {
-        // send request
-        self.send_message(Message::GetPower, false).await?;
+    /// Collects the devices responding to a discovery broadcast within `ms`
+    /// milliseconds.
+    pub async fn discover(ms: u64) -> Result<Vec<DiscoveredDevice>> {
+        let mut stream = Self::device_stream().await?;
+        let mut devices = Vec::new();
+
+        let collect = async {
+            while let Some(device) = stream.next().await {
+                devices.push(device);
+            }
+        };
+        let _ = timeout(collect, ms).await;
+
+        Ok(devices)
+    }
+
+    /// Discovers the devices with the given serials, waiting at most `ms`
+    /// milliseconds for all of them to respond.
+    pub async fn locate(serials: &[Serial], ms: u64) -> Result<Vec<DiscoveredDevice>> {
+        let mut stream = Self::device_stream().await?;
+        let mut devices = Vec::new();
+
+        let collect = async {
+            while let Some(device) = stream.next().await {
+                if serials.contains(&device.serial) {
+                    devices.push(device);
+                    if devices.len() == serials.len() {
+                        break;
+                    }
+                }
+            }
+        };
+        let _ = timeout(collect, ms).await;
 
-        // receive response
-        let response = self.receive_response().await?;
-        if let Some(Message::StatePower(power)) = response.message {
+        Ok(devices)
+    }
+
+    /// Returns whether the device is powered on.
+    pub async fn get_power(&mut self) -> Result<bool> {
+        let response = self.send_message(Message::GetPower, false).await?;
+        if let Message::StatePower(power) = response.message {
             Ok(power)
         } else {
-            Err(anyhow!(UNEXPECTED_PACKET))
+            Err(LifxError::UnexpectedPacket)
         }
     }
 
+    /// Turns the device on or off.
     pub async fn set_power(&mut self, power: bool) -> Result<()> {
-        self.send_message(Message::SetPower(power), true).await
+        self.send_message(Message::SetPower(power), true).await?;
+        Ok(())
     }
 
-    pub async fn get_label(&mut self) -> Result<String> {
-        self.send_message(Message::GetLabel, false).await?;
+    /// Returns whether the light is powered on.
+    pub async fn get_light_power(&mut self) -> Result<bool> {
+        let response = self.send_message(Message::GetLightPower, false).await?;
+        if let Message::StateLightPower(power) = response.message {
+            Ok(power)
+        } else {
+            Err(LifxError::UnexpectedPacket)
+        }
+    }
 
-        let response = self.receive_response().await?;
-        if let Some(Message::StateLabel(label)) = response.message {
+    /// Turns the light on or off, fading over `duration` milliseconds.
+    pub async fn set_light_power(&mut self, power: bool, duration: u32) -> Result<()> {
+        self.send_message(Message::SetLightPower(power, duration), true)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the label of the device.
+    pub async fn get_label(&mut self) -> Result<String> {
+        let response = self.send_message(Message::GetLabel, false).await?;
+        if let Message::StateLabel(label) = response.message {
             Ok(label)
         } else {
-            Err(anyhow!(UNEXPECTED_PACKET))
+            Err(LifxError::UnexpectedPacket)
         }
     }
 
+    /// Sets the label of the device.
     pub async fn set_label(&mut self, label: &str) -> Result<()> {
         self.send_message(Message::SetLabel(label.to_string()), true)
-            .await
+            .await?;
+        Ok(())
     }
 
-    pub async fn get_state(&mut self) -> Result<(u16, u16, u16, u16, bool, String)> {
-        self.send_message(Message::GetColor, false).await?;
+    /// Returns the location of the device.
+    pub async fn get_location(&mut self) -> Result<Collection> {
+        let response = self.send_message(Message::GetLocation, false).await?;
+        if let Message::StateLocation(location) = response.message {
+            Ok(location)
+        } else {
+            Err(LifxError::UnexpectedPacket)
+        }
+    }
+
+    /// Moves the device to a location.
+    pub async fn set_location(&mut self, location: &Collection) -> Result<()> {
+        self.send_message(Message::SetLocation(location.clone()), true)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the group of the device.
+    pub async fn get_group(&mut self) -> Result<Collection> {
+        let response = self.send_message(Message::GetGroup, false).await?;
+        if let Message::StateGroup(group) = response.message {
+            Ok(group)
+        } else {
+            Err(LifxError::UnexpectedPacket)
+        }
+    }
+
+    /// Moves the device to a group.
+    pub async fn set_group(&mut self, group: &Collection) -> Result<()> {
+        self.send_message(Message::SetGroup(group.clone()), true)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the host firmware of the device.
+    pub async fn get_host_firmware(&mut self) -> Result<HostFirmware> {
+        let response = self.send_message(Message::GetHostFirmware, false).await?;
+        if let Message::StateHostFirmware(firmware) = response.message {
+            Ok(firmware)
+        } else {
+            Err(LifxError::UnexpectedPacket)
+        }
+    }
+
+    /// Returns the Wi-Fi status of the device.
+    pub async fn get_wifi_info(&mut self) -> Result<WifiInfo> {
+        let response = self.send_message(Message::GetWifiInfo, false).await?;
+        if let Message::StateWifiInfo(info) = response.message {
+            Ok(info)
+        } else {
+            Err(LifxError::UnexpectedPacket)
+        }
+    }
+
+    /// Returns the hardware vendor and product of the device.
+    pub async fn get_version(&mut self) -> Result<Version> {
+        let response = self.send_message(Message::GetVersion, false).await?;
+        if let Message::StateVersion(version) = response.message {
+            Ok(version)
+        } else {
+            Err(LifxError::UnexpectedPacket)
+        }
+    }
+
+    /// Returns the product of the device from the built-in registry, or `None`
+    /// if the product is unknown.
+    ///
+    /// The product is only queried once per connection.
+    pub async fn get_product(&mut self) -> Result<Option<&'static Product>> {
+        if let Some(product) = self.product {
+            return Ok(product);
+        }
+
+        let version = self.get_version().await?;
+        let product = Product::lookup(version.vendor, version.product);
+        self.product = Some(product);
+        Ok(product)
+    }
+
+    /// Returns the capabilities of the device with its current firmware, or
+    /// `None` if the product is unknown.
+    ///
+    /// The capabilities are only queried once per connection.
+    pub async fn get_features(&mut self) -> Result<Option<Features>> {
+        if let Some(features) = self.features {
+            return Ok(features);
+        }
+
+        // The firmware only needs to be checked if it can add capabilities
+        let features = match self.get_product().await? {
+            Some(product) if product.upgrades.is_empty() => Some(product.features),
+            Some(product) => Some(product.features_for(&self.get_host_firmware().await?)),
+            None => None,
+        };
+        self.features = Some(features);
+        Ok(features)
+    }
+
+    /// Returns the current time, uptime and downtime of the device.
+    pub async fn get_info(&mut self) -> Result<DeviceInfo> {
+        let response = self.send_message(Message::GetInfo, false).await?;
+        if let Message::StateInfo(info) = response.message {
+            Ok(info)
+        } else {
+            Err(LifxError::UnexpectedPacket)
+        }
+    }
 
-        let response = self.receive_response().await?;
-        if let Some(Message::LightState(h, s, b, k, power, label)) = response.message {
-            Ok((h, s, b, k, power, label))
+    /// Returns the color, power and label of the device.
+    pub async fn get_state(&mut self) -> Result<LightState> {
+        let response = self.send_message(Message::GetColor, false).await?;
+        if let Message::LightState(state) = response.message {
+            Ok(state)
         } else {
-            Err(anyhow!(UNEXPECTED_PACKET))
+            Err(LifxError::UnexpectedPacket)
         }
     }
 
-    pub async fn set_color(
+    /// Sets the color of the device, transitioning over `duration` milliseconds.
+    ///
+    /// Fails if the product cannot display the color, and clamps the color
+    /// temperature to the range supported by the product.
+    pub async fn set_color(&mut self, color: Hsbk, duration: u32) -> Result<()> {
+        let color = self.adapt_color(color, true).await?;
+        self.send_message(Message::SetColor(color, duration), true)
+            .await?;
+        Ok(())
+    }
+
+    /// Performs a waveform effect on the device.
+    pub async fn set_waveform(&mut self, mut effect: WaveformEffect) -> Result<()> {
+        effect.color = self.adapt_color(effect.color, true).await?;
+        self.send_message(Message::SetWaveform(effect), true)
+            .await?;
+        Ok(())
+    }
+
+    /// Performs a waveform effect on the device, only changing the color
+    /// components selected by `mask`.
+    pub async fn set_waveform_optional(
         &mut self,
-        hue: u16,
-        saturation: u16,
-        brightness: u16,
-        kelvin: u16,
-        duration: u32,
+        mut effect: WaveformEffect,
+        mask: HsbkMask,
     ) -> Result<()> {
-        self.send_message(
-            Message::SetColor(hue, saturation, brightness, kelvin, duration),
-            true,
-        )
-        .await
+        self.check_color_change(effect.color, mask).await?;
+        effect.color = self.adapt_color(effect.color, false).await?;
+        self.send_message(Message::SetWaveformOptional(effect, mask), true)
+            .await?;
+        Ok(())
+    }
+
+    /// Fails if the components of `color` selected by `mask` change the hue
+    /// or saturation of a product that cannot display colors.
+    ///
+    /// Unknown products are assumed to support every color.
+    pub async fn check_color_change(&mut self, color: Hsbk, mask: HsbkMask) -> Result<()> {
+        // Removing the saturation is allowed, as the light is already white
+        let desaturated = mask.saturation && color.saturation() == 0.0;
+        if !(mask.hue || mask.saturation) || desaturated {
+            return Ok(());
+        }
+
+        match self.capabilities().await {
+            Some((product, features)) if !features.color => Err(LifxError::NotCapable {
+                product: product.name,
+                capability: "color",
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a color against the capabilities of the product.
+    ///
+    /// Unknown products are assumed to support every color.
+    async fn adapt_color(&mut self, mut color: Hsbk, check_saturation: bool) -> Result<Hsbk> {
+        let (product, features) = match self.capabilities().await {
+            Some(capabilities) => capabilities,
+            None => return Ok(color),
+        };
+
+        let (min, max) = features.temperature_range.ok_or(LifxError::NotCapable {
+            product: product.name,
+            capability: "lighting",
+        })?;
+        if check_saturation && !features.color && color.saturation() > 0.0 {
+            return Err(LifxError::NotCapable {
+                product: product.name,
+                capability: "color",
+            });
+        }
+
+        color.set_kelvin(color.kelvin().clamp(min, max));
+        Ok(color)
     }
 
-    async fn send_message(&mut self, message: Message, require_ack: bool) -> Result<()> {
-        let packet = message.encode(require_ack, self.sequence, None);
+    /// Returns the product and its capabilities, or `None` if the product is
+    /// unknown.
+    ///
+    /// A device that fails to report its product is treated like an unknown
+    /// product, and isn't asked again on this connection.
+    async fn capabilities(&mut self) -> Option<(&'static Product, Features)> {
+        let lookup = match self.get_product().await {
+            Ok(Some(product)) => self
+                .get_features()
+                .await
+                .map(|features| features.map(|features| (product, features))),
+            other => other.map(|_| None),
+        };
+        lookup.unwrap_or_else(|_| {
+            self.product = Some(None);
+            self.features = Some(None);
+            None
+        })
+    }
+
+    /// Sends a message and waits for the matching response.
+    ///
+    /// When `require_ack` is set the response is the device's acknowledgement,
+    /// otherwise it is the reply to the message itself. The message is resent
+    /// if no response arrives within the timeout, and a StateUnhandled reply
+    /// is reported as an unsupported operation.
+    async fn send_message(&mut self, message: Message, require_ack: bool) -> Result<Response> {
+        let sequence = self.sequence;
+        let packet = message.encode(self.source, require_ack, sequence, self.serial);
         self.sequence = self.sequence.wrapping_add(1);
-        self.sock.send_to(&packet, self.addr).await?;
 
-        if require_ack {
-            let _response = self.receive_response().await?;
+        for _ in 0..=self.retries {
+            self.sock.send_to(&packet, self.addr).await?;
+
+            let response = timeout(self.receive_response(sequence), self.timeout).await;
+            if let Timeout::Resolved(response) = response {
+                let response = response?;
+                return match response.message {
+                    Message::StateUnhandled(ty) => Err(LifxError::Unsupported(ty)),
+                    Message::Acknowledgement if require_ack => Ok(response),
+                    _ if require_ack => Err(LifxError::UnexpectedPacket),
+                    _ => Ok(response),
+                };
+            }
         }
 
-        Ok(())
+        Err(LifxError::NoResponse {
+            attempts: self.retries + 1,
+        })
     }
 
-    async fn receive_response(&self) -> Result<Response> {
+    async fn receive_response(&self, sequence: u8) -> Result<Response> {
         let mut buf = [0u8; 1024];
-        self.sock.recv(&mut buf).await?;
-        Ok(Response::decode(&buf)?)
+        loop {
+            let (len, addr) = self.sock.recv_from(&mut buf).await?;
+            if addr != self.addr {
+                continue;
+            }
+
+            // Discard malformed packets, and late or duplicate replies to
+            // earlier requests
+            let response = match Response::decode(&buf[..len]) {
+                Ok(response) => response,
+                Err(_) => continue,
+            };
+            let from_target = self.serial.map_or(true, |s| s.0 == response.target);
+            if response.source == self.source && response.sequence == sequence && from_target {
+                return Ok(response);
+            }
+        }
     }
 }
 
+/// Returns the source identifier used for all packets sent by this process.
+///
+/// Devices echo the source back in their replies, which lets us ignore
+/// packets meant for other clients.
+pub(crate) fn process_source() -> u32 {
+    static SOURCE: OnceLock<u32> = OnceLock::new();
+
+    *SOURCE.get_or_init(|| {
+        // Sources 0 and 1 make devices broadcast their replies
+        (crate::random_u64() as u32).max(2)
+    })
+}
+
+/// Service type advertised by devices for the LAN protocol over UDP.
+pub(crate) const SERVICE_UDP: u8 = 1;
+
+/// A device found during discovery.
+#[derive(Clone, Copy, Debug)]
+pub struct DiscoveredDevice {
+    /// Serial number of the device, taken from the response target.
+    pub serial: Serial,
+    /// Address of the device, using the advertised port.
+    pub address: SocketAddr,
+    /// Advertised service type.
+    pub service: u8,
+    /// Advertised port.
+    pub port: u32,
+}
+
+/// Stream of devices responding to a discovery broadcast.
 pub struct DeviceStream {
     sock: UdpSocket,
-    seen: HashSet<SocketAddr>,
+    source: u32,
+    seen: HashSet<Serial>,
 }
 
 impl Stream for DeviceStream {
-    type Item = SocketAddr;
+    type Item = DiscoveredDevice;
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        let mut buf = [0u8; 1024];
-        let mut rbuf = tokio::io::ReadBuf::new(&mut buf);
+        loop {
+            let mut buf = [0u8; 1024];
+            let mut rbuf = tokio::io::ReadBuf::new(&mut buf);
+
+            let addr = match self.sock.poll_recv_from(cx, &mut rbuf) {
+                Poll::Ready(Ok(addr)) => addr,
+                Poll::Ready(Err(_)) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
 
-        match self.sock.poll_recv_from(cx, &mut rbuf) {
-            Poll::Ready(Ok(addr)) if !self.seen.contains(&addr) => {
-                self.seen.insert(addr);
-                Poll::Ready(Some(addr))
+            // Ignore anything that isn't a well formed UDP StateService
+            let response = match Response::decode(rbuf.filled()) {
+                Ok(response) if response.source == self.source => response,
+                _ => continue,
+            };
+            let (service, port) = match response.message {
+                Message::StateService(service, port) if service == SERVICE_UDP => (service, port),
+                _ => continue,
+            };
+            let udp_port = match u16::try_from(port) {
+                Ok(p) if p > 0 => p,
+                _ => continue,
+            };
+
+            let serial = Serial(response.target);
+            if self.seen.insert(serial) {
+                return Poll::Ready(Some(DiscoveredDevice {
+                    serial,
+                    address: SocketAddr::new(addr.ip(), udp_port),
+                    service,
+                    port,
+                }));
             }
-            Poll::Ready(Ok(_)) => Poll::Pending,
-            Poll::Ready(Err(_)) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIAL: Serial = Serial(0x0000_ccbb_aad5_73d0);
+
+    /// Builds a StatePower reply with the given header fields.
+    fn state_power(source: u32, serial: Serial, sequence: u8, power: bool) -> Vec<u8> {
+        let mut packet = Message::SetPower(power).encode(source, false, sequence, Some(serial));
+        packet[32..34].copy_from_slice(&Message::StatePower(power).ty().to_le_bytes());
+        packet
+    }
+
+    /// Builds a StateVersion reply for a LIFX product.
+    fn state_version(source: u32, sequence: u8, product: u32) -> Vec<u8> {
+        let mut packet = Message::GetVersion.encode(source, false, sequence, Some(SERIAL));
+        packet[32..34].copy_from_slice(&33u16.to_le_bytes());
+        packet.extend(crate::LIFX_VENDOR.to_le_bytes());
+        packet.extend(product.to_le_bytes());
+        packet.extend(0u32.to_le_bytes());
+        let len = packet.len() as u16;
+        packet[..2].copy_from_slice(&len.to_le_bytes());
+        packet
+    }
+
+    async fn connect(device: &UdpSocket) -> LightConnection {
+        let mut conn = LightConnection::new(device.local_addr().unwrap())
+            .await
+            .unwrap();
+        conn.set_serial(SERIAL);
+        conn.set_timeout(50);
+        conn.set_retries(0);
+        conn
+    }
+
+    #[tokio::test]
+    async fn color_change_needs_color_product() {
+        let device = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut conn = connect(&device).await;
+        let source = conn.source;
+
+        let respond = async {
+            let mut buf = [0u8; 1024];
+            let (_, client) = device.recv_from(&mut buf).await.unwrap();
+            // LIFX White 800
+            let reply = state_version(source, buf[23], 10);
+            device.send_to(&reply, client).await.unwrap();
+        };
+
+        let hue = HsbkMask {
+            hue: true,
+            ..Default::default()
+        };
+        let (result, _) = tokio::join!(conn.check_color_change(Hsbk::default(), hue), respond);
+        assert!(matches!(
+            result,
+            Err(LifxError::NotCapable {
+                capability: "color",
+                ..
+            })
+        ));
+
+        // Removing the saturation and changing other components is allowed
+        let white = HsbkMask {
+            saturation: true,
+            kelvin: true,
+            ..Default::default()
+        };
+        let brightness = HsbkMask {
+            brightness: true,
+            ..Default::default()
+        };
+        let color = Hsbk::default();
+        assert!(conn.check_color_change(color, white).await.is_ok());
+        assert!(conn.check_color_change(color, brightness).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn color_is_unchanged_without_product() {
+        let device = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut conn = connect(&device).await;
+        let source = conn.source;
+        let color = Hsbk::from_raw(0x1234, 0x5678, 0x9abc, 9500);
+
+        let respond = async {
+            let mut buf = [0u8; 1024];
+            // The product lookup goes unanswered
+            device.recv_from(&mut buf).await.unwrap();
+
+            let (len, client) = device.recv_from(&mut buf).await.unwrap();
+            let ack = Message::Acknowledgement.encode(source, false, buf[23], Some(SERIAL));
+            device.send_to(&ack, client).await.unwrap();
+            buf[..len].to_vec()
+        };
+
+        let (result, packet) = tokio::join!(conn.set_color(color, 0), respond);
+        result.unwrap();
+        // Only the message type and payload are compared
+        let expected = Message::SetColor(color, 0).encode(source, true, 0, Some(SERIAL));
+        assert_eq!(packet[32..], expected[32..]);
+    }
+
+    #[tokio::test]
+    async fn unrelated_replies_are_ignored() {
+        let device = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut conn = LightConnection::new(device.local_addr().unwrap())
+            .await
+            .unwrap();
+        conn.set_serial(SERIAL);
+        conn.set_retries(0);
+        let source = conn.source;
+
+        let respond = async {
+            let mut buf = [0u8; 1024];
+            let (_, client) = device.recv_from(&mut buf).await.unwrap();
+            let sequence = buf[23];
+
+            let other_serial = Serial(SERIAL.0 + 1);
+            let replies = [
+                // Another client's request
+                state_power(source ^ 1, SERIAL, sequence, true),
+                // A late reply to the previous request
+                state_power(source, SERIAL, sequence.wrapping_sub(1), true),
+                // Another device at the same address
+                state_power(source, other_serial, sequence, true),
+                // A malformed packet
+                vec![0; 8],
+                state_power(source, SERIAL, sequence, false),
+            ];
+            for reply in &replies {
+                device.send_to(reply, client).await.unwrap();
+            }
+        };
+
+        let (power, _) = tokio::join!(conn.get_power(), respond);
+        assert!(!power.unwrap());
+    }
+}
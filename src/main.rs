@@ -1,43 +1,90 @@
-mod config;
-mod light;
-mod packet;
-mod util;
 use anyhow::{anyhow, Context, Result};
 use clap::{App, AppSettings, Arg, ArgMatches};
-use futures::StreamExt;
+use futures::{future::join_all, StreamExt};
+use lifxc::{
+    CachedDevice, Collection, ColorSpec, Config, ConfigDocument, Device, DeviceSnapshot,
+    DiscoveredDevice, DiscoveryCache, Hsbk, HsbkMask, LifxError, LightConnection, NamedSnapshot,
+    Product, Scene, SceneState, Serial, SnapshotStore, StateSnapshot, Waveform, WaveformEffect,
+};
+use serde::Serialize;
 use std::net::SocketAddr;
-
-pub use config::*;
-pub use light::*;
-pub use packet::*;
-pub use util::*;
-
-pub const LIFX_PORT: u16 = 56700;
+use std::sync::Mutex;
+use std::time::Duration;
 
 const DEVICE: &str = "device";
+const ALL: &str = "all";
+const GROUP_FILTER: &str = "group-filter";
+const LOCATION_FILTER: &str = "location-filter";
 const TIMEOUT: &str = "timeout";
+const DISCOVERY_TIMEOUT: &str = "discovery-timeout";
+const RETRIES: &str = "retries";
 const DURATION: &str = "duration";
+const REFRESH: &str = "refresh";
+const WRITE_CONFIG: &str = "write-config";
+const OUTPUT: &str = "output";
 const DISCOVER: &str = "discover";
 const POWER: &str = "power";
 const TOGGLE: &str = "toggle";
 const LABEL: &str = "label";
 const BRIGHTNESS: &str = "brightness";
 const COLOR: &str = "color";
+const EFFECT: &str = "effect";
+const INFO: &str = "info";
+const GROUP: &str = "group";
+const LOCATION: &str = "location";
+const WAVEFORM: &str = "waveform";
+const PERIOD: &str = "period";
+const CYCLES: &str = "cycles";
+const SKEW_RATIO: &str = "skew-ratio";
+const TRANSIENT: &str = "transient";
+
+const CONFIG: &str = "config";
+const ADD_DEVICE: &str = "add-device";
+const REMOVE_DEVICE: &str = "remove-device";
+const SET_DEFAULT: &str = "set-default";
+const LIST: &str = "list";
+const PATH: &str = "path";
+const VALIDATE: &str = "validate";
+const ALIAS: &str = "alias";
+const ADDRESS: &str = "address";
+const SERIAL: &str = "serial";
+
+const SCENE: &str = "scene";
+const APPLY: &str = "apply";
+const SAVE: &str = "save";
+const SNAPSHOT: &str = "snapshot";
+const RESTORE: &str = "restore";
+const NAME: &str = "name";
+
+const DEFAULT_DISCOVERY_TIMEOUT: u64 = 1000;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Load config
-    let config = Config::load()
-        .await
-        .context("Unable to parse configuration file")?;
-
     let device_arg = Arg::new(DEVICE)
-        .about("Address or alias of device to control")
+        .about("Addresses, serials, aliases, labels or groups of devices to control, separated by commas")
         .env("LIFXC_DEVICE")
         .long("device")
         .short('d')
+        .takes_value(true)
+        .multiple_occurrences(true)
+        .use_delimiter(true);
+
+    let all_arg = Arg::new(ALL)
+        .about("Control all devices on your local network")
+        .long("all");
+
+    let group_arg = Arg::new(GROUP_FILTER)
+        .about("Control all devices in the group with this label")
+        .long("group")
         .takes_value(true);
 
+    let location_arg = Arg::new(LOCATION_FILTER)
+        .about("Control all devices in the location with this label")
+        .long("location")
+        .takes_value(true);
+
+    let target_args = [device_arg, all_arg, group_arg, location_arg];
+
     let matches = App::new("lifxc")
         .version("0.1.0")
         .author("Harrison Rigg <riggh@icloud.com>")
@@ -46,21 +93,47 @@ async fn main() -> Result<()> {
         .global_setting(AppSettings::InferSubcommands)
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .setting(AppSettings::DisableHelpSubcommand)
+        .args(&[
+            Arg::new(TIMEOUT)
+                .about("Timeout (in milliseconds) for each request")
+                .long("timeout")
+                .short('t')
+                .global(true)
+                .takes_value(true),
+            Arg::new(DISCOVERY_TIMEOUT)
+                .about("Time (in milliseconds) to wait for devices to respond to discovery")
+                .long("discovery-timeout")
+                .global(true)
+                .takes_value(true),
+            Arg::new(RETRIES)
+                .about("Number of times to retry a request before giving up")
+                .long("retries")
+                .global(true)
+                .takes_value(true),
+            Arg::new(OUTPUT)
+                .about("Format to print results in")
+                .long("output")
+                .short('o')
+                .global(true)
+                .possible_values(["plain", "json", "toml"])
+                .default_value("plain"),
+        ])
         .subcommand(
             App::new(DISCOVER)
                 .about("Discover devices on your local network")
-                .arg(
-                    Arg::new(TIMEOUT)
-                        .about("Timeout (in milliseconds) for device discovery")
-                        .long("timeout")
-                        .short('t')
-                        .default_value("1000"),
-                ),
+                .args(&[
+                    Arg::new(REFRESH)
+                        .about("Forget previously discovered devices that did not respond")
+                        .long("refresh"),
+                    Arg::new(WRITE_CONFIG)
+                        .about("Add discovered devices to the configuration file")
+                        .long("write-config"),
+                ]),
         )
         .subcommand(
             App::new(LABEL)
                 .about("Get or set label of the specified device")
-                .arg(&device_arg)
+                .args(&target_args)
                 .arg(
                     Arg::new(LABEL)
                         .about("Label to assign to device")
@@ -71,23 +144,34 @@ async fn main() -> Result<()> {
         .subcommand(
             App::new(POWER)
                 .about("Get or set power state of the specified device")
-                .arg(&device_arg)
-                .arg(
+                .args(&target_args)
+                .args(&[
                     Arg::new(POWER)
                         .about("Power state to set device")
                         .long("set")
                         .possible_values(["on", "off"]),
-                ),
+                    Arg::new(DURATION)
+                        .about("Duration (in milliseconds) of power transition")
+                        .long("duration")
+                        .requires(POWER)
+                        .takes_value(true),
+                ]),
         )
         .subcommand(
             App::new(TOGGLE)
                 .about("Toggle power state of the specified device")
-                .arg(&device_arg),
+                .args(&target_args)
+                .arg(
+                    Arg::new(DURATION)
+                        .about("Duration (in milliseconds) of power transition")
+                        .long("duration")
+                        .takes_value(true),
+                ),
         )
         .subcommand(
             App::new(BRIGHTNESS)
                 .about("Get or set brightness of the specified device")
-                .arg(&device_arg)
+                .args(&target_args)
                 .args(&[
                     Arg::new(BRIGHTNESS)
                         .about("Brightness (in percent) to set device")
@@ -103,8 +187,11 @@ async fn main() -> Result<()> {
         .subcommand(
             App::new(COLOR)
                 .about("Get or set color of the specified device")
-                .arg(&device_arg)
+                .args(&target_args)
                 .args(&[
+                    Arg::new(COLOR).about(
+                        "Color to set device, as a name, #rrggbb, rgb:R,G,B, hsb:H,S,B or kelvin:K",
+                    ),
                     Arg::new("hue")
                         .about("Hue (in degrees) to set device")
                         .long("hue")
@@ -127,136 +214,1737 @@ async fn main() -> Result<()> {
                         .takes_value(true),
                 ]),
         )
+        .subcommand(
+            App::new(INFO)
+                .about("Show hardware and firmware information of the specified device")
+                .args(&target_args),
+        )
+        .subcommand(
+            App::new(EFFECT)
+                .about("Perform a waveform effect on the specified device")
+                .args(&target_args)
+                .args(&[
+                    Arg::new(WAVEFORM)
+                        .about("Shape of the effect")
+                        .required(true)
+                        .possible_values(["saw", "sine", "half-sine", "triangle", "pulse"]),
+                    Arg::new("hue")
+                        .about("Hue (in degrees) of effect color")
+                        .long("hue")
+                        .takes_value(true),
+                    Arg::new("saturation")
+                        .about("Saturation (in percent) of effect color")
+                        .long("saturation")
+                        .takes_value(true),
+                    Arg::new("brightness")
+                        .about("Brightness (in percent) of effect color")
+                        .long("brightness")
+                        .takes_value(true),
+                    Arg::new("kelvin")
+                        .about("Color temperature (in kelvin) of effect color")
+                        .long("kelvin")
+                        .takes_value(true),
+                    Arg::new(PERIOD)
+                        .about("Duration (in milliseconds) of a single cycle")
+                        .long("period")
+                        .default_value("1000"),
+                    Arg::new(CYCLES)
+                        .about("Number of cycles to perform")
+                        .long("cycles")
+                        .default_value("1"),
+                    Arg::new(SKEW_RATIO)
+                        .about("Fraction (0 to 1) of each pulse cycle spent on the original color")
+                        .long("skew-ratio")
+                        .default_value("0.5"),
+                    Arg::new(TRANSIENT)
+                        .about("Return to the original color after the effect")
+                        .long("transient"),
+                ]),
+        )
+        .subcommand(
+            App::new(GROUP)
+                .about("Get or set group of the specified device")
+                .args(&target_args)
+                .arg(
+                    Arg::new(GROUP)
+                        .about("Label of group to move device to")
+                        .long("set")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new(LOCATION)
+                .about("Get or set location of the specified device")
+                .args(&target_args)
+                .arg(
+                    Arg::new(LOCATION)
+                        .about("Label of location to move device to")
+                        .long("set")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new(SCENE)
+                .about("Apply or save scenes in the configuration file")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    App::new(APPLY)
+                        .about("Set every device in a scene to its state in the scene")
+                        .args(&[
+                            Arg::new(NAME).about("Name of the scene").required(true),
+                            Arg::new(DURATION)
+                                .about("Duration (in milliseconds) of the transition, overriding the scene")
+                                .long("duration")
+                                .takes_value(true),
+                        ]),
+                )
+                .subcommand(
+                    App::new(SAVE)
+                        .about("Save the current state of the specified devices as a scene")
+                        .args(&target_args)
+                        .args(&[
+                            Arg::new(NAME)
+                                .about("Name of the scene, replacing any with the same name")
+                                .required(true),
+                            Arg::new(DURATION)
+                                .about("Duration (in milliseconds) of the transition to the scene")
+                                .long("duration")
+                                .takes_value(true),
+                        ]),
+                ),
+        )
+        .subcommand(
+            App::new(SNAPSHOT)
+                .about("Save the state of devices and restore it later")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    App::new(SAVE)
+                        .about("Save the color and power of the specified devices")
+                        .args(&target_args)
+                        .arg(
+                            Arg::new(NAME)
+                                .about("Name of the snapshot, replacing any with the same name")
+                                .default_value("default"),
+                        ),
+                )
+                .subcommand(
+                    App::new(RESTORE)
+                        .about("Restore the color and power of every device in a snapshot")
+                        .args(&[
+                            Arg::new(NAME)
+                                .about("Name of the snapshot")
+                                .default_value("default"),
+                            Arg::new(DURATION)
+                                .about("Duration (in milliseconds) of the transition")
+                                .long("duration")
+                                .default_value("0"),
+                        ]),
+                ),
+        )
+        .subcommand(
+            App::new(CONFIG)
+                .about("View or edit the configuration file")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    App::new(ADD_DEVICE)
+                        .about("Add a device with an alias")
+                        .args(&[
+                            Arg::new(ALIAS)
+                                .about("Alias to refer to the device by")
+                                .required(true),
+                            Arg::new(ADDRESS)
+                                .about("IP address of the device")
+                                .long("address")
+                                .takes_value(true),
+                            Arg::new(SERIAL)
+                                .about("Serial number of the device")
+                                .long("serial")
+                                .takes_value(true),
+                        ]),
+                )
+                .subcommand(
+                    App::new(REMOVE_DEVICE)
+                        .about("Remove the device with an alias")
+                        .arg(
+                            Arg::new(ALIAS)
+                                .about("Alias of the device to remove")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    App::new(SET_DEFAULT)
+                        .about("Set the device controlled when none is specified")
+                        .arg(
+                            Arg::new(DEVICE)
+                                .about("Address, serial, alias or group of the device, but not a label")
+                                .required(true),
+                        ),
+                )
+                .subcommand(App::new(LIST).about("List configured devices and groups"))
+                .subcommand(App::new(PATH).about("Print the location of the configuration file"))
+                .subcommand(App::new(VALIDATE).about("Check the configuration file for errors")),
+        )
         .get_matches();
 
+    let result = run(&matches).await;
+    if let Err(ref e) = result {
+        // Errors are reported in the same format as results, unless the
+        // results of each device have already been printed
+        let mut leaf = &matches;
+        while let Some((_, sm)) = leaf.subcommand() {
+            leaf = sm;
+        }
+        let format = OutputFormat::from_matches(leaf);
+        if format != OutputFormat::Plain && e.downcast_ref::<DevicesFailed>().is_none() {
+            format.print(&CommandResult {
+                success: false,
+                message: None,
+                error: Some(format!("{:#}", e)),
+            })?;
+            std::process::exit(1);
+        }
+    }
+    result
+}
+
+/// Runs the subcommand given on the command line.
+async fn run(matches: &ArgMatches) -> Result<()> {
+    if let Some((CONFIG, sm)) = matches.subcommand() {
+        // An invalid configuration file mustn't prevent it being fixed
+        return configure(sm).await;
+    }
+
+    // Load config
+    let config = Config::load()
+        .await
+        .context("Unable to parse configuration file")?;
+
     match matches.subcommand() {
         Some((DISCOVER, sm)) => {
-            let device_stream = LightConnection::device_stream().await?;
-            let fut = device_stream.for_each(|d| async move {
-                let mut conn = LightConnection::new(d).await.unwrap();
-                let (_, _, _, _, _, label) = conn.get_state().await.unwrap();
-                println!("Found device: {} {}", label, d);
+            // --timeout used to set the discovery time, so is refused rather
+            // than silently changing meaning
+            if sm.is_present(TIMEOUT) {
+                return Err(anyhow!(
+                    "--timeout is the timeout for each request, use --discovery-timeout to wait longer for devices."
+                ));
+            }
+            let tm = discovery_timeout(&config, sm)?;
+
+            // Each device is queried as soon as it responds, and any queries still
+            // running at the end of the timeout are waited for
+            let config = &config;
+            let deadline = tokio::time::sleep(Duration::from_millis(tm));
+            let mut found: Vec<FoundDevice> = LightConnection::device_stream()
+                .await?
+                .take_until(deadline)
+                .map(|device| query_device(config, sm, device))
+                .buffer_unordered(usize::MAX)
+                .collect()
+                .await;
+            found.sort_by_cached_key(|d| {
+                (
+                    d.label.is_none(),
+                    d.label.as_deref().map(str::to_lowercase),
+                    d.serial,
+                )
             });
 
-            let tm = sm.value_of_t(TIMEOUT).unwrap();
-            let _ = timeout(fut, tm).await;
-        }
-        Some((LABEL, sm)) => {
-            let device = find_device(&config, sm)?;
-            let mut conn = LightConnection::new(device).await?;
+            let mut cache = DiscoveryCache::load().await;
+            if sm.is_present(REFRESH) {
+                cache.clear();
+            }
+            for device in &found {
+                let entry = cache.insert(&device.discovered);
+                if let Some(ref label) = device.label {
+                    entry.label = Some(label.clone());
+                }
+                if let Some(product) = device.product {
+                    entry.product = Some(product.to_string());
+                }
+            }
 
-            if let Some(label) = sm.value_of(LABEL) {
-                conn.set_label(label).await?;
-                println!("Success");
-            } else {
-                let label = conn.get_label().await?;
-                println!("{}", label);
+            let format = OutputFormat::from_matches(sm);
+            if format == OutputFormat::Plain {
+                print_devices(&found);
             }
-        }
-        Some((POWER, sm)) => {
-            let device = find_device(&config, sm)?;
-            let mut conn = LightConnection::new(device).await?;
 
-            if let Some(power) = sm.value_of(POWER) {
-                conn.set_power(power == "on").await?;
-            } else {
-                let power = conn.get_power().await?;
-                println!("{}", if power { "on" } else { "off" });
+            cache
+                .save()
+                .await
+                .context("Unable to write discovery cache")?;
+
+            if sm.is_present(WRITE_CONFIG) {
+                let mut doc = ConfigDocument::load()
+                    .await
+                    .context("Unable to parse configuration file")?;
+                for device in &mut found {
+                    device.added_alias =
+                        doc.import_device(device.serial, device.address, device.label.as_deref())?;
+                }
+
+                let added: Vec<&FoundDevice> =
+                    found.iter().filter(|d| d.added_alias.is_some()).collect();
+                if !added.is_empty() {
+                    doc.save()
+                        .await
+                        .context("Unable to write configuration file")?;
+                }
+                if format == OutputFormat::Plain {
+                    for device in added {
+                        println!(
+                            "Added device '{}' ({}) to the configuration file.",
+                            device.added_alias.as_deref().unwrap_or_default(),
+                            device.serial
+                        );
+                    }
+                }
             }
+            format.print(&Devices { devices: found })?;
         }
-        Some((TOGGLE, sm)) => {
-            let device = find_device(&config, sm)?;
-            let mut conn = LightConnection::new(device).await?;
+        Some((SCENE, sm)) => match sm.subcommand() {
+            Some((APPLY, sm)) => {
+                let name = sm.value_of(NAME).unwrap();
+                let scene = config
+                    .find_scene(name)
+                    .ok_or_else(|| anyhow!("No scene is named '{}'.", name))?;
+                let duration = sm
+                    .value_of(DURATION)
+                    .map(|d| d.parse::<u32>())
+                    .transpose()?
+                    .unwrap_or(scene.duration);
 
-            let power = conn.get_power().await?;
-            conn.set_power(!power).await?;
-        }
-        Some((BRIGHTNESS, sm)) => {
-            let device = find_device(&config, sm)?;
-            let mut conn = LightConnection::new(device).await?;
+                // Later entries override earlier ones for the same device
+                let names: Vec<&str> = scene.devices.iter().map(|s| s.device.as_str()).collect();
+                let resolved = resolve_each(&config, sm, &names).await?;
+                let mut states: Vec<(Target, ColorOverrides, Option<bool>)> = Vec::new();
+                for (state, targets) in scene.devices.iter().zip(resolved) {
+                    for target in targets {
+                        let index = match states
+                            .iter()
+                            .position(|(t, _, _)| t.is_same_device(&target))
+                        {
+                            Some(index) => index,
+                            None => {
+                                states.push((target, ColorOverrides::default(), None));
+                                states.len() - 1
+                            }
+                        };
 
-            if let Ok(b) = sm.value_of_t::<f32>(BRIGHTNESS) {
-                let (h, s, _, k, ..) = conn.get_state().await?;
-                let b = (b / 100.0) * 0x10000 as f32;
+                        let (_, overrides, power) = &mut states[index];
+                        overrides.hue = state.hue.or(overrides.hue);
+                        overrides.saturation = state.saturation.or(overrides.saturation);
+                        overrides.brightness = state.brightness.or(overrides.brightness);
+                        overrides.kelvin = state.kelvin.or(overrides.kelvin);
+                        *power = state.power.or(*power);
+                    }
+                }
+
+                let (targets, commands): (Vec<_>, Vec<_>) = states
+                    .into_iter()
+                    .map(|(target, overrides, power)| {
+                        (target, Command::SetState(overrides, power, duration))
+                    })
+                    .unzip();
+                let jobs: Vec<_> = targets.into_iter().zip(&commands).collect();
+                run_all(&config, sm, &jobs).await?;
+            }
+            Some((SAVE, sm)) => {
+                let name = sm.value_of(NAME).unwrap();
                 let duration = sm
                     .value_of(DURATION)
                     .map(|d| d.parse::<u32>())
                     .transpose()?
                     .unwrap_or(0);
-                conn.set_color(h, s, b as u16, k, duration).await?;
-            } else {
-                let (_, _, b, ..) = conn.get_state().await?;
-                println!("{:.1}%", 100.0 * b as f32 / 0x10000 as f32);
+                let targets = find_devices(&config, sm).await?;
+
+                let config = &config;
+                let states = join_all(targets.iter().map(|target| async move {
+                    let mut conn = connect(config, sm, target).await?;
+                    Ok::<_, anyhow::Error>(conn.get_state().await?)
+                }))
+                .await;
+
+                let mut devices = Vec::new();
+                for (target, state) in targets.iter().zip(states) {
+                    let state =
+                        state.with_context(|| format!("Unable to get state of {}", target.name))?;
+                    devices.push(SceneState {
+                        device: config_name(config, target),
+                        power: Some(state.power),
+                        hue: Some(state.color.hue()),
+                        saturation: Some(state.color.saturation()),
+                        brightness: Some(state.color.brightness()),
+                        kelvin: Some(state.color.kelvin()),
+                    });
+                }
+
+                let mut doc = load_document().await?;
+                doc.set_scene(&Scene {
+                    name: name.to_string(),
+                    duration,
+                    devices,
+                })?;
+                save_document(&doc).await?;
+                report(sm, &format!("Saved scene '{}'.", name))?;
             }
+            _ => (),
+        },
+        Some((SNAPSHOT, sm)) => match sm.subcommand() {
+            Some((SAVE, sm)) => {
+                let name = sm.value_of(NAME).unwrap();
+                let targets = find_devices(&config, sm).await?;
+
+                let config = &config;
+                let states = join_all(targets.iter().map(|target| async move {
+                    let mut conn = connect(config, sm, target).await?;
+                    Ok::<_, anyhow::Error>(StateSnapshot::capture(&mut conn).await?)
+                }))
+                .await;
+
+                let mut devices = Vec::new();
+                for (target, state) in targets.iter().zip(states) {
+                    let state =
+                        state.with_context(|| format!("Unable to get state of {}", target.name))?;
+                    devices.push(DeviceSnapshot {
+                        serial: target.serial,
+                        address: target.address,
+                        state,
+                    });
+                }
+
+                let mut store = SnapshotStore::load()
+                    .await
+                    .context("Unable to read snapshots")?;
+                store.insert(NamedSnapshot::new(name, devices));
+                store.save().await.context("Unable to write snapshots")?;
+                report(sm, &format!("Saved snapshot '{}'.", name))?;
+            }
+            Some((RESTORE, sm)) => {
+                let name = sm.value_of(NAME).unwrap();
+                let duration = sm.value_of(DURATION).unwrap().parse::<u32>()?;
+                let store = SnapshotStore::load()
+                    .await
+                    .context("Unable to read snapshots")?;
+                let snapshot = store
+                    .find(name)
+                    .ok_or_else(|| anyhow!("No snapshot is named '{}'.", name))?;
+
+                // Devices with a serial may have moved since the snapshot was
+                // taken, so are relocated like cached devices if they don't respond
+                let commands: Vec<Command> = snapshot
+                    .devices
+                    .iter()
+                    .map(|d| Command::Restore(d.state, duration))
+                    .collect();
+                let jobs: Vec<_> = snapshot
+                    .devices
+                    .iter()
+                    .map(|d| Target {
+                        name: d.serial.map_or(d.address.to_string(), |s| s.to_string()),
+                        address: d.address,
+                        serial: d.serial,
+                        cached: d.serial.is_some(),
+                    })
+                    .zip(&commands)
+                    .collect();
+                run_all(&config, sm, &jobs).await?;
+            }
+            _ => (),
+        },
+        Some((name, sm)) => {
+            let command = Command::parse(&config, name, sm).await?;
+            let targets = find_devices(&config, sm).await?;
+
+            let jobs: Vec<_> = targets.into_iter().map(|t| (t, &command)).collect();
+            run_all(&config, sm, &jobs).await?;
         }
-        Some((COLOR, sm)) => {
-            let device = find_device(&config, sm)?;
-            let mut conn = LightConnection::new(device).await?;
+        _ => (),
+    }
 
-            let hue = sm.value_of("hue").map(|h| h.parse::<f32>()).transpose()?;
-            let saturation = sm
-                .value_of("saturation")
-                .map(|s| s.parse::<f32>())
-                .transpose()?;
-            let brightness = sm
-                .value_of("brightness")
-                .map(|b| b.parse::<f32>())
-                .transpose()?;
-            let kelvin = sm
-                .value_of("kelvin")
-                .map(|k| k.parse::<u16>())
+    Ok(())
+}
+
+/// Runs a subcommand of the config subcommand.
+async fn configure(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some((ADD_DEVICE, sm)) => {
+            let address = sm
+                .value_of(ADDRESS)
+                .map(|a| Device::parse_address(a).ok_or_else(|| anyhow!("Invalid IP address.")))
                 .transpose()?;
+            let serial = sm.value_of(SERIAL).map(str::parse::<Serial>).transpose()?;
+
+            let alias = sm.value_of(ALIAS).unwrap();
+            let mut doc = load_document().await?;
+            doc.add_device(alias, address, serial)?;
+            save_document(&doc).await?;
+            report(sm, &format!("Added device '{}'.", alias))?;
+        }
+        Some((REMOVE_DEVICE, sm)) => {
+            let alias = sm.value_of(ALIAS).unwrap();
+            let mut doc = load_document().await?;
+            doc.remove_device(alias)?;
+            save_document(&doc).await?;
+            report(sm, &format!("Removed device '{}'.", alias))?;
+        }
+        Some((SET_DEFAULT, sm)) => {
+            let device = sm.value_of(DEVICE).unwrap();
+            let mut doc = load_document().await?;
+            doc.set_default_device(device);
+            save_document(&doc).await?;
+            report(sm, &format!("Set default device to '{}'.", device))?;
+        }
+        Some((LIST, sm)) => {
+            let config = Config::load()
+                .await
+                .context("Unable to parse configuration file")?;
+
+            let format = OutputFormat::from_matches(sm);
+            if format != OutputFormat::Plain {
+                return format.print(&config);
+            }
+
+            if let Some(ref device) = config.default_device {
+                println!("Default device: {}", device);
+            }
+            if !config.devices.is_empty() {
+                let mut rows = vec![["ALIAS", "ADDRESS", "SERIAL"].map(str::to_string)];
+                for device in &config.devices {
+                    rows.push([
+                        device.alias.clone(),
+                        device.address.map_or("-".to_string(), |a| a.to_string()),
+                        device.serial.map_or("-".to_string(), |s| s.to_string()),
+                    ]);
+                }
+                print_table(&rows);
+            }
+            if !config.groups.is_empty() {
+                let mut rows = vec![["GROUP", "MEMBERS"].map(str::to_string)];
+                for group in &config.groups {
+                    rows.push([group.name.clone(), group.members.join(", ")]);
+                }
+                print_table(&rows);
+            }
+        }
+        Some((PATH, sm)) => {
+            let path = Config::path()
+                .ok_or_else(|| anyhow!("Unable to locate the configuration directory."))?;
+            report(sm, &path.display().to_string())?;
+        }
+        Some((VALIDATE, sm)) => {
+            Config::load()
+                .await
+                .context("Configuration file is invalid")?;
+            report(sm, "Configuration file is valid.")?;
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+async fn load_document() -> Result<ConfigDocument> {
+    ConfigDocument::load()
+        .await
+        .context("Unable to parse configuration file")
+}
+
+async fn save_document(doc: &ConfigDocument) -> Result<()> {
+    doc.save().await.with_context(|| {
+        format!(
+            "Unable to update configuration file {}",
+            doc.path().display()
+        )
+    })
+}
 
-            if hue.is_some() || saturation.is_some() || brightness.is_some() || kelvin.is_some() {
-                let (mut h, mut s, mut b, mut k, ..) = conn.get_state().await?;
+/// Runs a command on each device concurrently and reports the outcome.
+async fn run_all(config: &Config, matches: &ArgMatches, jobs: &[(Target, &Command)]) -> Result<()> {
+    let relocated = Mutex::new(Vec::new());
+    let relocated_ref = &relocated;
+    let mut results = join_all(jobs.iter().map(|(target, command)| async move {
+        match run_command(config, matches, command, target).await {
+            Err(e) if target.cached && is_no_response(&e) => {
+                // The cached address may be stale, so locate the device again
+                let device = relocate(config, matches, target).await?;
+                relocated_ref.lock().unwrap().push(device);
+                let target = Target {
+                    address: device.address,
+                    cached: false,
+                    ..target.clone()
+                };
+                run_command(config, matches, command, &target).await
+            }
+            result => result,
+        }
+    }))
+    .await;
 
-                if let Some(hue) = hue {
-                    h = (hue * 0x10000 as f32 / 360.0) as u16;
+    // Renamed devices must no longer be found by their old label
+    let relocated = relocated.into_inner().unwrap();
+    let renamed: Vec<(&Target, &str)> = jobs
+        .iter()
+        .zip(&results)
+        .filter_map(|((target, command), result)| match (command, result) {
+            (Command::SetLabel(label), Ok(_)) => Some((target, label.as_str())),
+            _ => None,
+        })
+        .collect();
+    if !relocated.is_empty() || !renamed.is_empty() {
+        let mut cache = DiscoveryCache::load().await;
+        for device in &relocated {
+            cache.insert(device);
+        }
+        for (target, label) in renamed {
+            cache.set_label(target.serial, target.address, label);
+        }
+        save_cache(&cache).await;
+    }
+
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    let format = OutputFormat::from_matches(matches);
+    if format != OutputFormat::Plain {
+        let devices = jobs
+            .iter()
+            .zip(&results)
+            .map(|((target, _), result)| DeviceResult {
+                device: &target.name,
+                address: target.address,
+                serial: target.serial,
+                success: result.is_ok(),
+                output: result.as_ref().ok().and_then(Option::as_ref),
+                error: result.as_ref().err().map(|e| e.to_string()),
+            })
+            .collect();
+        format.print(&Devices { devices })?;
+    } else if results.len() == 1 {
+        if let Some(output) = results.remove(0)? {
+            println!("{}", output);
+        }
+        return Ok(());
+    } else {
+        // Summarise the outcome for each device
+        for ((target, _), result) in jobs.iter().zip(results) {
+            match result.map(|o| o.map(|o| o.to_string())) {
+                Ok(Some(output)) if !output.contains('\n') => {
+                    println!("{}: {}", target.name, output)
                 }
-                if let Some(saturation) = saturation {
-                    s = (saturation * 0x10000 as f32 / 100.0) as u16;
+                Ok(Some(output)) => {
+                    println!("{}:", target.name);
+                    for line in output.lines() {
+                        println!("  {}", line);
+                    }
                 }
-                if let Some(brightness) = brightness {
-                    b = (brightness * 0x10000 as f32 / 100.0) as u16;
+                Ok(None) => println!("{}: Success", target.name),
+                Err(e) => println!("{}: Error: {}", target.name, e),
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(DevicesFailed {
+            failed,
+            total: jobs.len(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Error for a command which failed on some devices, once the outcome for
+/// each device has been reported.
+#[derive(Debug)]
+struct DevicesFailed {
+    failed: usize,
+    total: usize,
+}
+
+impl std::fmt::Display for DevicesFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Command failed on {} of {} devices.",
+            self.failed, self.total
+        )
+    }
+}
+
+impl std::error::Error for DevicesFailed {}
+
+async fn run_command(
+    config: &Config,
+    matches: &ArgMatches,
+    command: &Command,
+    target: &Target,
+) -> Result<Option<Output>> {
+    let mut conn = connect(config, matches, target).await?;
+    command.run(&mut conn).await
+}
+
+fn is_no_response(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<LifxError>(),
+        Some(LifxError::NoResponse { .. })
+    )
+}
+
+/// Saves the discovery cache as a side effect of another command.
+///
+/// The cache only speeds up finding devices, so failing to write it doesn't
+/// affect the command.
+async fn save_cache(cache: &DiscoveryCache) {
+    let _ = cache.save().await;
+}
+
+/// Finds the current address of a device through discovery.
+async fn relocate(
+    config: &Config,
+    matches: &ArgMatches,
+    target: &Target,
+) -> Result<DiscoveredDevice> {
+    let serials: Vec<Serial> = target.serial.into_iter().collect();
+    LightConnection::locate(&serials, discovery_timeout(config, matches)?)
+        .await?
+        .pop()
+        .ok_or_else(|| anyhow!("Unable to find device '{}' on the network.", target.name))
+}
+
+/// Action to perform on each device.
+enum Command {
+    GetLabel,
+    SetLabel(String),
+    GetPower,
+    SetPower(bool, Option<u32>),
+    Toggle(Option<u32>),
+    GetBrightness,
+    SetBrightness(f32, u32),
+    GetColor,
+    SetColor(ColorOverrides, u32),
+    /// Sets the color and power of a device, both with the given duration.
+    SetState(ColorOverrides, Option<bool>, u32),
+    Restore(StateSnapshot, u32),
+    Effect(WaveformEffect, HsbkMask),
+    Info,
+    GetCollection(CollectionKind),
+    SetCollection(CollectionKind, Collection),
+}
+
+impl Command {
+    async fn parse(config: &Config, name: &str, matches: &ArgMatches) -> Result<Command> {
+        let duration = matches
+            .value_of(DURATION)
+            .map(|d| d.parse::<u32>())
+            .transpose()?;
+
+        Ok(match name {
+            LABEL => match matches.value_of(LABEL) {
+                Some(label) => Command::SetLabel(label.to_string()),
+                None => Command::GetLabel,
+            },
+            POWER => match matches.value_of(POWER) {
+                Some(power) => Command::SetPower(power == "on", duration),
+                None => Command::GetPower,
+            },
+            TOGGLE => Command::Toggle(duration),
+            BRIGHTNESS => match matches.value_of(BRIGHTNESS) {
+                Some(b) => Command::SetBrightness(b.parse()?, duration.unwrap_or(0)),
+                None => Command::GetBrightness,
+            },
+            COLOR => {
+                let spec = match matches.value_of(COLOR) {
+                    Some(spec) => spec.parse::<ColorSpec>()?.into(),
+                    None => ColorOverrides::default(),
+                };
+
+                // Individual components take precedence over the color spec
+                let overrides = ColorOverrides::parse(matches)?.or(spec);
+                if overrides.is_empty() {
+                    Command::GetColor
+                } else {
+                    Command::SetColor(overrides, duration.unwrap_or(0))
                 }
-                if let Some(kelvin) = kelvin {
-                    k = kelvin;
+            }
+            EFFECT => {
+                let waveform = match matches.value_of(WAVEFORM) {
+                    Some("saw") => Waveform::Saw,
+                    Some("sine") => Waveform::Sine,
+                    Some("half-sine") => Waveform::HalfSine,
+                    Some("triangle") => Waveform::Triangle,
+                    _ => Waveform::Pulse,
+                };
+
+                // Only the specified components of the color are changed
+                let overrides = ColorOverrides::parse(matches)?;
+                if overrides.is_empty() {
+                    return Err(anyhow!("No effect color specified."));
                 }
 
-                let duration = sm
-                    .value_of(DURATION)
-                    .map(|d| d.parse::<u32>())
-                    .transpose()?
-                    .unwrap_or(0);
+                let mut color = Hsbk::default();
+                overrides.apply(&mut color);
+
+                let effect = WaveformEffect {
+                    waveform,
+                    color,
+                    transient: matches.is_present(TRANSIENT),
+                    period: matches.value_of_t(PERIOD)?,
+                    cycles: matches.value_of_t(CYCLES)?,
+                    skew_ratio: matches.value_of_t(SKEW_RATIO)?,
+                };
+                Command::Effect(effect, overrides.mask())
+            }
+            INFO => Command::Info,
+            GROUP | LOCATION => {
+                let kind = if name == GROUP {
+                    CollectionKind::Group
+                } else {
+                    CollectionKind::Location
+                };
 
-                conn.set_color(h, s, b, k, duration).await?;
-            } else {
-                let (h, s, b, k, ..) = conn.get_state().await?;
-                println!("Hue: {:.1}", 360.0 * h as f32 / 0x10000 as f32);
-                println!("Saturation: {:.1}%", 100.0 * s as f32 / 0x10000 as f32);
-                println!("Brightness: {:.1}%", 100.0 * b as f32 / 0x10000 as f32);
-                println!("Kelvin: {}", k);
+                match matches.value_of(name) {
+                    Some(label) => {
+                        // Join an existing collection with the same label if there is one
+                        let devices = discover_targets(config, matches).await?;
+                        let existing = discover_collections(config, matches, &devices, kind)
+                            .await?
+                            .into_iter()
+                            .map(|(_, c)| c)
+                            .filter(|c| c.label.eq_ignore_ascii_case(label))
+                            .max_by_key(|c| c.updated_at);
+                        let collection = existing.unwrap_or_else(|| Collection::new(label));
+                        Command::SetCollection(kind, collection)
+                    }
+                    None => Command::GetCollection(kind),
+                }
             }
+            _ => return Err(anyhow!("Unknown subcommand.")),
+        })
+    }
+
+    /// Runs the command, returning the text to print.
+    async fn run(&self, conn: &mut LightConnection) -> Result<Option<Output>> {
+        Ok(match self {
+            Command::GetLabel => Some(Output::Label {
+                label: conn.get_label().await?,
+            }),
+            Command::SetLabel(label) => {
+                conn.set_label(label).await?;
+                None
+            }
+            Command::GetPower => Some(Output::Power {
+                power: conn.get_power().await?,
+            }),
+            Command::SetPower(power, duration) => {
+                set_power(conn, *power, *duration).await?;
+                None
+            }
+            Command::Toggle(duration) => {
+                let power = conn.get_power().await?;
+                set_power(conn, !power, *duration).await?;
+                None
+            }
+            Command::GetBrightness => {
+                let color = conn.get_state().await?.color;
+                Some(Output::Brightness {
+                    brightness: round(color.brightness()),
+                })
+            }
+            Command::SetBrightness(brightness, duration) => {
+                let mut color = conn.get_state().await?.color;
+                color.set_brightness(*brightness);
+                conn.set_color(color, *duration).await?;
+                None
+            }
+            Command::GetColor => {
+                let color = conn.get_state().await?.color;
+                Some(Output::Color(color.into()))
+            }
+            Command::SetColor(overrides, duration) => {
+                let mut color = conn.get_state().await?.color;
+                overrides.apply(&mut color);
+                conn.check_color_change(color, overrides.mask()).await?;
+                conn.set_color(color, *duration).await?;
+                None
+            }
+            Command::SetState(overrides, power, duration) => {
+                if !overrides.is_empty() {
+                    let mut color = if overrides.is_complete() {
+                        Hsbk::default()
+                    } else {
+                        conn.get_state().await?.color
+                    };
+                    overrides.apply(&mut color);
+                    conn.check_color_change(color, overrides.mask()).await?;
+                    conn.set_color(color, *duration).await?;
+                }
+                if let Some(power) = power {
+                    conn.set_light_power(*power, *duration).await?;
+                }
+                None
+            }
+            Command::Restore(snapshot, duration) => {
+                snapshot.restore(conn, *duration).await?;
+                None
+            }
+            Command::Effect(effect, mask) => {
+                conn.set_waveform_optional(*effect, *mask).await?;
+                None
+            }
+            Command::Info => {
+                let version = conn.get_version().await?;
+                let product = Product::lookup(version.vendor, version.product);
+                let firmware = conn.get_host_firmware().await?;
+                let wifi = conn.get_wifi_info().await?;
+                let info = conn.get_info().await?;
+
+                Some(Output::Info {
+                    vendor: version.vendor,
+                    product_id: version.product,
+                    product: product.map(|p| p.name),
+                    firmware: format!("{}.{}", firmware.version_major, firmware.version_minor),
+                    signal: wifi.rssi(),
+                    uptime: info.uptime().as_secs(),
+                })
+            }
+            Command::GetCollection(kind) => {
+                let label = kind.get(conn).await?.label;
+                Some(match kind {
+                    CollectionKind::Group => Output::Group { group: label },
+                    CollectionKind::Location => Output::Location { location: label },
+                })
+            }
+            Command::SetCollection(kind, collection) => {
+                kind.set(conn, collection).await?;
+                None
+            }
+        })
+    }
+}
+
+/// Result of a query on a single device.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Output {
+    Label {
+        label: String,
+    },
+    Power {
+        power: bool,
+    },
+    Brightness {
+        brightness: f32,
+    },
+    Color(ColorOutput),
+    Info {
+        vendor: u32,
+        product_id: u32,
+        product: Option<&'static str>,
+        firmware: String,
+        /// Signal strength (in dBm), if the device reported one.
+        signal: Option<i32>,
+        /// Uptime (in seconds).
+        uptime: u64,
+    },
+    Group {
+        group: String,
+    },
+    Location {
+        location: String,
+    },
+}
+
+impl std::fmt::Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Output::Label { label } => write!(f, "{}", label),
+            Output::Power { power } => write!(f, "{}", if *power { "on" } else { "off" }),
+            Output::Brightness { brightness } => write!(f, "{:.1}%", brightness),
+            Output::Color(color) => write!(
+                f,
+                "Hue: {:.1}\nSaturation: {:.1}%\nBrightness: {:.1}%\nKelvin: {}",
+                color.hue, color.saturation, color.brightness, color.kelvin
+            ),
+            Output::Info {
+                vendor,
+                product_id,
+                product,
+                firmware,
+                signal,
+                uptime,
+            } => {
+                writeln!(f, "Vendor: {}", vendor)?;
+                match product {
+                    Some(product) => writeln!(f, "Product: {} ({})", product, product_id)?,
+                    None => writeln!(f, "Product: {}", product_id)?,
+                }
+                writeln!(f, "Firmware: {}", firmware)?;
+                match signal {
+                    Some(signal) => writeln!(f, "Signal: {} dBm", signal)?,
+                    None => writeln!(f, "Signal: unknown")?,
+                }
+                write!(
+                    f,
+                    "Uptime: {}",
+                    format_duration(Duration::from_secs(*uptime))
+                )
+            }
+            Output::Group { group } => write!(f, "{}", group),
+            Output::Location { location } => write!(f, "{}", location),
         }
-        _ => (),
     }
+}
 
+/// Color of a device, rounded for output.
+#[derive(Serialize)]
+struct ColorOutput {
+    hue: f32,
+    saturation: f32,
+    brightness: f32,
+    kelvin: u16,
+}
+
+impl ColorOutput {
+    /// Formats the color on a single line.
+    fn summary(&self) -> String {
+        format!(
+            "{:.1}° {:.1}% {:.1}% {}K",
+            self.hue, self.saturation, self.brightness, self.kelvin
+        )
+    }
+}
+
+impl From<Hsbk> for ColorOutput {
+    fn from(color: Hsbk) -> ColorOutput {
+        ColorOutput {
+            hue: round(color.hue()),
+            saturation: round(color.saturation()),
+            brightness: round(color.brightness()),
+            kelvin: color.kelvin(),
+        }
+    }
+}
+
+/// Outcome of a command on a single device, for structured output.
+#[derive(Serialize)]
+struct DeviceResult<'a> {
+    device: &'a str,
+    address: SocketAddr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    serial: Option<Serial>,
+    success: bool,
+    #[serde(flatten)]
+    output: Option<&'a Output>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Outcome of a command which doesn't run on devices, for structured output.
+#[derive(Serialize)]
+struct CommandResult {
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Reports the success of a command which doesn't run on devices.
+fn report(matches: &ArgMatches, message: &str) -> Result<()> {
+    match OutputFormat::from_matches(matches) {
+        OutputFormat::Plain => println!("{}", message),
+        format => format.print(&CommandResult {
+            success: true,
+            message: Some(message.to_string()),
+            error: None,
+        })?,
+    }
     Ok(())
 }
 
-fn find_device(config: &Config, matches: &ArgMatches) -> Result<SocketAddr> {
-    if let Some(device) = matches.value_of(DEVICE) {
+/// A device found by discovery, with whichever details it responded with.
+#[derive(Serialize)]
+struct FoundDevice {
+    #[serde(skip)]
+    discovered: DiscoveredDevice,
+    serial: Serial,
+    address: SocketAddr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    product: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    firmware: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    power: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Alias the device was added to the configuration file with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    added_alias: Option<String>,
+    // Tables must come after all other values in TOML
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<ColorOutput>,
+}
+
+/// Queries the details of a discovered device, keeping those received before
+/// any error.
+async fn query_device(
+    config: &Config,
+    matches: &ArgMatches,
+    device: DiscoveredDevice,
+) -> FoundDevice {
+    let mut found = FoundDevice {
+        discovered: device,
+        serial: device.serial,
+        address: device.address,
+        label: None,
+        product: None,
+        firmware: None,
+        power: None,
+        error: None,
+        added_alias: None,
+        color: None,
+    };
+
+    let result = async {
+        let mut conn = connect(config, matches, &Target::from(device)).await?;
+        let state = conn.get_state().await?;
+        found.label = Some(state.label);
+        found.power = Some(state.power);
+        found.color = Some(state.color.into());
+
+        found.product = conn.get_product().await?.map(|p| p.name);
+        let firmware = conn.get_host_firmware().await?;
+        found.firmware = Some(format!(
+            "{}.{}",
+            firmware.version_major, firmware.version_minor
+        ));
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    found.error = result.err().map(|e| e.to_string());
+    found
+}
+
+/// Prints discovered devices as an aligned table, followed by any errors.
+fn print_devices(devices: &[FoundDevice]) {
+    let unknown = || "-".to_string();
+    let mut rows = vec![[
+        "LABEL", "SERIAL", "ADDRESS", "PRODUCT", "FIRMWARE", "POWER", "COLOR",
+    ]
+    .map(str::to_string)];
+    for device in devices {
+        rows.push([
+            device.label.clone().unwrap_or_else(unknown),
+            device.serial.to_string(),
+            device.address.to_string(),
+            device.product.map(str::to_string).unwrap_or_else(unknown),
+            device.firmware.clone().unwrap_or_else(unknown),
+            device
+                .power
+                .map(|p| if p { "on" } else { "off" }.to_string())
+                .unwrap_or_else(unknown),
+            device
+                .color
+                .as_ref()
+                .map(ColorOutput::summary)
+                .unwrap_or_else(unknown),
+        ]);
+    }
+
+    print_table(&rows);
+
+    for device in devices {
+        if let Some(ref e) = device.error {
+            println!("{}: Error: {}", device.serial, e);
+        }
+    }
+}
+
+/// Prints rows with each column aligned.
+fn print_table<const N: usize>(rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+/// Top level of structured output, which must be a table for TOML.
+#[derive(Serialize)]
+struct Devices<T> {
+    devices: Vec<T>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Plain,
+    Json,
+    Toml,
+}
+
+impl OutputFormat {
+    fn from_matches(matches: &ArgMatches) -> OutputFormat {
+        match matches.value_of(OUTPUT) {
+            Some("json") => OutputFormat::Json,
+            Some("toml") => OutputFormat::Toml,
+            _ => OutputFormat::Plain,
+        }
+    }
+
+    /// Prints a value in a structured format, or nothing in plain format.
+    fn print<T: Serialize>(self, value: &T) -> Result<()> {
+        match self {
+            OutputFormat::Plain => (),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+            OutputFormat::Toml => print!("{}", toml::to_string(value)?),
+        }
+        Ok(())
+    }
+}
+
+/// Rounds a color component to one decimal place.
+fn round(value: f32) -> f32 {
+    (value * 10.0).round() / 10.0
+}
+
+/// Either of the two ways devices can be grouped on the devices themselves.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CollectionKind {
+    Group,
+    Location,
+}
+
+impl CollectionKind {
+    async fn get(self, conn: &mut LightConnection) -> lifxc::Result<Collection> {
+        match self {
+            CollectionKind::Group => conn.get_group().await,
+            CollectionKind::Location => conn.get_location().await,
+        }
+    }
+
+    async fn set(self, conn: &mut LightConnection, collection: &Collection) -> lifxc::Result<()> {
+        match self {
+            CollectionKind::Group => conn.set_group(collection).await,
+            CollectionKind::Location => conn.set_location(collection).await,
+        }
+    }
+}
+
+/// Queries the group or location of each device.
+///
+/// Devices which do not respond are skipped.
+async fn discover_collections(
+    config: &Config,
+    matches: &ArgMatches,
+    devices: &[Target],
+    kind: CollectionKind,
+) -> Result<Vec<(Target, Collection)>> {
+    let queries = devices.iter().map(|device| async move {
+        let mut conn = connect(config, matches, device).await?;
+        let collection = kind.get(&mut conn).await?;
+        Ok::<_, anyhow::Error>((device.clone(), collection))
+    });
+
+    Ok(join_all(queries)
+        .await
+        .into_iter()
+        .filter_map(|r| r.ok())
+        .collect())
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) =
+        (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+
+    if days > 0 {
+        format!("{}d {}h {}m {}s", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Color components to change, from the command line or a scene.
+#[derive(Default)]
+struct ColorOverrides {
+    hue: Option<f32>,
+    saturation: Option<f32>,
+    brightness: Option<f32>,
+    kelvin: Option<u16>,
+}
+
+impl ColorOverrides {
+    fn parse(matches: &ArgMatches) -> Result<ColorOverrides> {
+        let hue = matches
+            .value_of("hue")
+            .map(|h| h.parse::<f32>())
+            .transpose()?;
+        let saturation = matches
+            .value_of("saturation")
+            .map(|s| s.parse::<f32>())
+            .transpose()?;
+        let brightness = matches
+            .value_of("brightness")
+            .map(|b| b.parse::<f32>())
+            .transpose()?;
+        let kelvin = matches
+            .value_of("kelvin")
+            .map(|k| k.parse::<u16>())
+            .transpose()?;
+
+        Ok(ColorOverrides {
+            hue,
+            saturation,
+            brightness,
+            kelvin,
+        })
+    }
+
+    /// Fills in components missing from `self` with those from `other`.
+    fn or(self, other: ColorOverrides) -> ColorOverrides {
+        ColorOverrides {
+            hue: self.hue.or(other.hue),
+            saturation: self.saturation.or(other.saturation),
+            brightness: self.brightness.or(other.brightness),
+            kelvin: self.kelvin.or(other.kelvin),
+        }
+    }
+
+    fn mask(&self) -> HsbkMask {
+        HsbkMask {
+            hue: self.hue.is_some(),
+            saturation: self.saturation.is_some(),
+            brightness: self.brightness.is_some(),
+            kelvin: self.kelvin.is_some(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.mask() == HsbkMask::default()
+    }
+
+    fn is_complete(&self) -> bool {
+        let mask = self.mask();
+        mask.hue && mask.saturation && mask.brightness && mask.kelvin
+    }
+
+    fn apply(&self, color: &mut Hsbk) {
+        if let Some(hue) = self.hue {
+            color.set_hue(hue);
+        }
+        if let Some(saturation) = self.saturation {
+            color.set_saturation(saturation);
+        }
+        if let Some(brightness) = self.brightness {
+            color.set_brightness(brightness);
+        }
+        if let Some(kelvin) = self.kelvin {
+            color.set_kelvin(kelvin);
+        }
+    }
+}
+
+impl From<ColorSpec> for ColorOverrides {
+    fn from(spec: ColorSpec) -> ColorOverrides {
+        match spec {
+            ColorSpec::Hsb(hue, saturation, brightness) => ColorOverrides {
+                hue: Some(hue),
+                saturation: Some(saturation),
+                brightness: Some(brightness),
+                kelvin: None,
+            },
+            // White is a desaturated color at the given temperature
+            ColorSpec::Kelvin(kelvin) => ColorOverrides {
+                saturation: Some(0.0),
+                kelvin: Some(kelvin),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+async fn set_power(conn: &mut LightConnection, power: bool, duration: Option<u32>) -> Result<()> {
+    // Only lights support transitions, so other devices use the plain message
+    match duration {
+        Some(duration) => conn.set_light_power(power, duration).await?,
+        None => conn.set_power(power).await?,
+    }
+
+    Ok(())
+}
+
+async fn connect(
+    config: &Config,
+    matches: &ArgMatches,
+    target: &Target,
+) -> Result<LightConnection> {
+    let mut conn = LightConnection::new(target.address).await?;
+    if let Some(serial) = target.serial {
+        conn.set_serial(serial);
+    }
+
+    // Command line flags take precedence over the configuration file
+    let timeout = matches
+        .value_of(TIMEOUT)
+        .map(|t| t.parse::<u64>())
+        .transpose()?
+        .or(config.timeout);
+    if let Some(timeout) = timeout {
+        conn.set_timeout(timeout);
+    }
+
+    let retries = matches
+        .value_of(RETRIES)
+        .map(|r| r.parse::<u32>())
+        .transpose()?
+        .or(config.retries);
+    if let Some(retries) = retries {
+        conn.set_retries(retries);
+    }
+
+    Ok(conn)
+}
+
+/// Returns how long (in milliseconds) to wait for devices to respond to a
+/// discovery broadcast.
+fn discovery_timeout(config: &Config, matches: &ArgMatches) -> Result<u64> {
+    // Command line flags take precedence over the configuration file
+    let timeout = matches
+        .value_of(DISCOVERY_TIMEOUT)
+        .map(|t| t.parse::<u64>())
+        .transpose()?
+        .or(config.discovery_timeout)
+        .unwrap_or(DEFAULT_DISCOVERY_TIMEOUT);
+    Ok(timeout)
+}
+
+/// Returns the name to refer to a device by in the configuration file, which
+/// must be an alias, serial or address.
+fn config_name(config: &Config, target: &Target) -> String {
+    if config.find_alias(&target.name).is_some() {
+        target.name.clone()
+    } else if let Some(serial) = target.serial {
+        serial.to_string()
+    } else {
+        target.address.to_string()
+    }
+}
+
+/// A device selected on the command line.
+#[derive(Clone)]
+struct Target {
+    /// Name to refer to the device by in output.
+    name: String,
+    address: SocketAddr,
+    serial: Option<Serial>,
+    /// Whether the address was taken from the discovery cache.
+    cached: bool,
+}
+
+impl Target {
+    /// Returns whether both targets refer to the same device, comparing serials
+    /// if both are known and addresses otherwise.
+    fn is_same_device(&self, other: &Target) -> bool {
+        match (self.serial, other.serial) {
+            (Some(serial), Some(other)) => serial == other,
+            _ => self.address == other.address,
+        }
+    }
+}
+
+impl From<DiscoveredDevice> for Target {
+    fn from(device: DiscoveredDevice) -> Target {
+        Target {
+            name: device.address.to_string(),
+            address: device.address,
+            serial: Some(device.serial),
+            cached: false,
+        }
+    }
+}
+
+async fn find_devices(config: &Config, matches: &ArgMatches) -> Result<Vec<Target>> {
+    let group = matches.value_of(GROUP_FILTER);
+    let location = matches.value_of(LOCATION_FILTER);
+    // Devices from the environment are a default, like the configured device
+    let named = matches.occurrences_of(DEVICE) > 0;
+    if matches.is_present(ALL) && named {
+        return Err(anyhow!("--all cannot be used with --device."));
+    }
+    if !matches.is_present(ALL) && group.is_none() && location.is_none() {
+        return find_named_devices(config, matches).await;
+    }
+
+    // Devices must belong to every collection specified, and the group and
+    // location narrow down any named devices
+    let mut members = if named {
+        find_named_devices(config, matches).await?
+    } else {
+        discover_targets(config, matches).await?
+    };
+    for (kind, label) in [
+        (CollectionKind::Group, group),
+        (CollectionKind::Location, location),
+    ] {
+        let label = match label {
+            Some(label) => label,
+            None => continue,
+        };
+
+        members = discover_collections(config, matches, &members, kind)
+            .await?
+            .into_iter()
+            .filter(|(_, c)| c.label.eq_ignore_ascii_case(label))
+            .map(|(target, _)| target)
+            .collect();
+    }
+
+    if members.is_empty() {
+        return Err(anyhow!("No matching devices found."));
+    }
+    members.sort_by_key(|t| t.address);
+    Ok(members)
+}
+
+async fn discover_targets(config: &Config, matches: &ArgMatches) -> Result<Vec<Target>> {
+    let devices = LightConnection::discover(discovery_timeout(config, matches)?).await?;
+    Ok(devices.into_iter().map(Target::from).collect())
+}
+
+async fn find_named_devices(config: &Config, matches: &ArgMatches) -> Result<Vec<Target>> {
+    let devices: Vec<&str> = if let Some(devices) = matches.values_of(DEVICE) {
         // Passed as argument or environment variable
-        if let Some(addr) = config.find_alias(device) {
-            Ok(addr)
-        } else {
-            parse_address(device)
-                .ok_or_else(|| anyhow!("Device is neither a valid IP address or alias."))
-        }
-    } else if let Some(device) = config.default_device {
-        Ok(device)
+        devices.map(str::trim).filter(|d| !d.is_empty()).collect()
     } else {
+        config.default_device.as_deref().into_iter().collect()
+    };
+    if devices.is_empty() {
         // No device set
-        Err(anyhow!("No device address specified."))
+        return Err(anyhow!("No device address specified."));
     }
+
+    resolve_devices(config, matches, &devices).await
+}
+
+/// Finds the address and serial of each named device or group, without
+/// duplicates.
+async fn resolve_devices(
+    config: &Config,
+    matches: &ArgMatches,
+    devices: &[&str],
+) -> Result<Vec<Target>> {
+    let mut targets: Vec<Target> = Vec::new();
+    for target in resolve_each(config, matches, devices)
+        .await?
+        .into_iter()
+        .flatten()
+    {
+        if !targets.iter().any(|t| t.is_same_device(&target)) {
+            targets.push(target);
+        }
+    }
+    Ok(targets)
+}
+
+/// Finds the devices for each named device or group separately, sharing any
+/// label lookup and discovery between them.
+async fn resolve_each(
+    config: &Config,
+    matches: &ArgMatches,
+    devices: &[&str],
+) -> Result<Vec<Vec<Target>>> {
+    let mut found: Vec<(usize, &str, Option<SocketAddr>, Option<Serial>)> = Vec::new();
+    for (i, &device) in devices.iter().enumerate() {
+        // Groups expand to all of their members
+        let members = match config.find_group(device) {
+            Some(group) => group.members.iter().map(String::as_str).collect(),
+            None => vec![device],
+        };
+
+        for member in members {
+            let (address, serial) = find_device(config, member);
+            found.push((i, member, address, serial));
+        }
+    }
+
+    // Devices given by label are resolved to their serial
+    let mut cache = DiscoveryCache::load().await;
+    let mut labels: Vec<&str> = found
+        .iter()
+        .filter(|(_, _, address, serial)| address.is_none() && serial.is_none())
+        .map(|(_, name, _, _)| *name)
+        .collect();
+    labels.sort_unstable();
+    labels.dedup();
+    if !labels.is_empty() {
+        let serials = find_labels(config, matches, &mut cache, &labels).await?;
+        for (_, name, _, serial) in &mut found {
+            if let Some(i) = labels.iter().position(|label| label == name) {
+                *serial = Some(serials[i]);
+            }
+        }
+    }
+
+    // Devices known only by serial are looked up in the discovery cache, then
+    // any remaining are located with a single discovery broadcast
+    let mut unknown: Vec<Serial> = found
+        .iter()
+        .filter(|(_, _, address, _)| address.is_none())
+        .filter_map(|(_, _, _, serial)| *serial)
+        .filter(|serial| cache.find(*serial).is_none())
+        .collect();
+    unknown.sort();
+    unknown.dedup();
+    if !unknown.is_empty() {
+        for device in LightConnection::locate(&unknown, discovery_timeout(config, matches)?).await?
+        {
+            cache.insert(&device);
+        }
+        save_cache(&cache).await;
+    }
+
+    let mut targets: Vec<Vec<Target>> = vec![Vec::new(); devices.len()];
+    for (i, name, address, serial) in found {
+        let (address, cached) = match (address, serial) {
+            (Some(address), _) => (address, false),
+            (None, Some(serial)) => match cache.find(serial) {
+                Some(device) => (device.address, !unknown.contains(&serial)),
+                None => return Err(anyhow!("Unable to find device '{}' on the network.", name)),
+            },
+            (None, None) => unreachable!("labels are resolved to serials"),
+        };
+
+        let target = Target {
+            name: name.to_string(),
+            address,
+            serial,
+            cached,
+        };
+        if !targets[i].iter().any(|t| t.is_same_device(&target)) {
+            targets[i].push(target);
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Looks up the address and serial of a device alias, address or serial.
+///
+/// Neither is returned for any other name, which is treated as a device label.
+fn find_device(config: &Config, device: &str) -> (Option<SocketAddr>, Option<Serial>) {
+    if let Some(device) = config.find_alias(device) {
+        (device.address, device.serial)
+    } else if let Some(address) = Device::parse_address(device) {
+        (Some(address), None)
+    } else if let Ok(serial) = device.parse::<Serial>() {
+        (None, Some(serial))
+    } else {
+        (None, None)
+    }
+}
+
+/// Finds the serial of the device with each label, ignoring case.
+///
+/// Labels are looked up in the discovery cache and confirmed with the devices,
+/// falling back to querying the label of every device on the network if any
+/// label is unknown or has changed.
+async fn find_labels(
+    config: &Config,
+    matches: &ArgMatches,
+    cache: &mut DiscoveryCache,
+    labels: &[&str],
+) -> Result<Vec<Serial>> {
+    let cached: Option<Vec<&CachedDevice>> = labels
+        .iter()
+        .map(|label| match labelled(cache.devices(), label)[..] {
+            [device] => Some(device),
+            _ => None,
+        })
+        .collect();
+    if let Some(cached) = cached {
+        // The cached label is stale if the device has since been renamed
+        let confirmed = join_all(cached.iter().zip(labels).map(|(device, label)| async move {
+            let target = Target {
+                name: label.to_string(),
+                address: device.address,
+                serial: Some(device.serial),
+                cached: true,
+            };
+            let mut conn = connect(config, matches, &target).await.ok()?;
+            let current = conn.get_label().await.ok()?;
+            Some(current.eq_ignore_ascii_case(label))
+        }))
+        .await;
+        if confirmed.into_iter().all(|c| c == Some(true)) {
+            return Ok(cached.iter().map(|d| d.serial).collect());
+        }
+    }
+
+    let devices = LightConnection::discover(discovery_timeout(config, matches)?).await?;
+    let queried = join_all(devices.iter().map(|&device| async move {
+        let mut conn = connect(config, matches, &Target::from(device)).await?;
+        conn.get_label().await.map_err(anyhow::Error::from)
+    }))
+    .await;
+
+    for (device, label) in devices.iter().zip(queried) {
+        let entry = cache.insert(device);
+        entry.label = label.ok();
+    }
+    save_cache(cache).await;
+
+    // Only the devices that just responded are matched, so that stale labels of
+    // other cached devices are ignored
+    let found: Vec<CachedDevice> = devices
+        .iter()
+        .filter_map(|device| cache.find(device.serial).cloned())
+        .collect();
+    labels
+        .iter()
+        .map(|label| match labelled(&found, label)[..] {
+            [] => Err(anyhow!(
+                "Device '{}' is neither a valid IP address, serial, alias or label.",
+                label
+            )),
+            [device] => Ok(device.serial),
+            ref candidates => {
+                let candidates: Vec<String> = candidates
+                    .iter()
+                    .map(|d| format!("{} ({})", d.label.as_deref().unwrap_or_default(), d.serial))
+                    .collect();
+                Err(anyhow!(
+                    "Label '{}' matches multiple devices: {}.",
+                    label,
+                    candidates.join(", ")
+                ))
+            }
+        })
+        .collect()
+}
+
+/// Returns the devices with the label, ignoring case.
+fn labelled<'a>(devices: &'a [CachedDevice], label: &str) -> Vec<&'a CachedDevice> {
+    devices
+        .iter()
+        .filter(|d| {
+            d.label
+                .as_deref()
+                .is_some_and(|l| l.eq_ignore_ascii_case(label))
+        })
+        .collect()
 }
@@ -1,6 +1,7 @@
-use anyhow::{anyhow, ensure, Result};
-
-const PACKET_ERROR: &str = "Bad packet received from device.";
+use crate::{
+    Collection, DeviceInfo, HostFirmware, Hsbk, HsbkMask, LifxError, Result, Version,
+    WaveformEffect, WifiInfo,
+};
 
 fn read_u16(input: &[u8]) -> u16 {
     u16::from_le_bytes([input[0], input[1]])
@@ -16,49 +17,181 @@ fn read_u64(input: &[u8]) -> u64 {
     ])
 }
 
+fn ensure(valid: bool) -> Result<()> {
+    if valid {
+        Ok(())
+    } else {
+        Err(LifxError::MalformedPacket)
+    }
+}
+
 fn read_lifx_str(input: &[u8]) -> Result<&str> {
     let last = input
         .iter()
         .position(|b| *b == 0)
-        .ok_or(anyhow!(PACKET_ERROR))?;
-    std::str::from_utf8(&input[..last]).map_err(|_| anyhow!(PACKET_ERROR))
+        .ok_or(LifxError::MalformedPacket)?;
+    std::str::from_utf8(&input[..last]).map_err(|_| LifxError::MalformedPacket)
+}
+
+fn write_lifx_str(input: &str) -> Vec<u8> {
+    let mut chars = input.as_bytes().to_vec();
+    chars.resize(32, 0);
+    chars
+}
+
+fn read_collection(input: &[u8]) -> Result<Collection> {
+    let mut id = [0u8; 16];
+    id.copy_from_slice(&input[..16]);
+    let label = read_lifx_str(&input[16..48])?;
+    let updated_at = read_u64(&input[48..]);
+
+    Ok(Collection {
+        id,
+        label: label.to_string(),
+        updated_at,
+    })
 }
 
+/// A LIFX LAN protocol message.
+#[non_exhaustive]
 pub enum Message {
+    /// Requests the services of a device, broadcast for discovery.
     GetService,
+    /// Advertised service type and port.
+    StateService(u8, u32),
+
+    /// Requests the host firmware version.
+    GetHostFirmware,
+    /// Host firmware version.
+    StateHostFirmware(HostFirmware),
+
+    /// Requests the Wi-Fi status.
+    GetWifiInfo,
+    /// Wi-Fi status.
+    StateWifiInfo(WifiInfo),
+
+    /// Requests the location of a device.
+    GetLocation,
+    /// Sets the location of a device.
+    SetLocation(Collection),
+    /// Location of a device.
+    StateLocation(Collection),
+
+    /// Requests the group of a device.
+    GetGroup,
+    /// Sets the group of a device.
+    SetGroup(Collection),
+    /// Group of a device.
+    StateGroup(Collection),
+
+    /// Requests the vendor and product IDs.
+    GetVersion,
+    /// Vendor and product IDs.
+    StateVersion(Version),
+
+    /// Requests the time, uptime and downtime.
+    GetInfo,
+    /// Time, uptime and downtime.
+    StateInfo(DeviceInfo),
+
+    /// Acknowledgement of a request which asked for one.
+    Acknowledgement,
+    /// Reply to a request of the given message type which is not supported.
+    StateUnhandled(u16),
 
+    /// Requests the power level.
     GetPower,
+    /// Sets the power level.
     SetPower(bool),
+    /// Whether the device is on.
     StatePower(bool),
 
+    /// Requests the label.
     GetLabel,
+    /// Sets the label.
     SetLabel(String),
+    /// Label of the device.
     StateLabel(String),
 
+    /// Requests the color, power and label of a light.
     GetColor,
-    SetColor(u16, u16, u16, u16, u32),
-    LightState(u16, u16, u16, u16, bool, String),
+    /// Sets the color of a light, over a duration in milliseconds.
+    SetColor(Hsbk, u32),
+    /// Color, power and label of a light.
+    LightState(crate::LightState),
+    /// Performs a waveform effect.
+    SetWaveform(WaveformEffect),
+    /// Performs a waveform effect on the selected color components.
+    SetWaveformOptional(WaveformEffect, HsbkMask),
 
+    /// Requests the power level of a light.
+    GetLightPower,
+    /// Sets the power level of a light, over a duration in milliseconds.
+    SetLightPower(bool, u32),
+    /// Whether the light is on.
+    StateLightPower(bool),
+
+    /// A message of a type this library doesn't decode.
     Unknown,
 }
 
 impl Message {
     const GET_SERVICE: u16 = 0x02;
+    const STATE_SERVICE: u16 = 0x03;
+    const GET_HOST_FIRMWARE: u16 = 0x0E;
+    const STATE_HOST_FIRMWARE: u16 = 0x0F;
+    const GET_WIFI_INFO: u16 = 0x10;
+    const STATE_WIFI_INFO: u16 = 0x11;
+    const ACKNOWLEDGEMENT: u16 = 0x2D;
+    const GET_LOCATION: u16 = 0x30;
+    const SET_LOCATION: u16 = 0x31;
+    const STATE_LOCATION: u16 = 0x32;
+    const GET_GROUP: u16 = 0x33;
+    const SET_GROUP: u16 = 0x34;
+    const STATE_GROUP: u16 = 0x35;
     const GET_POWER: u16 = 0x14;
     const SET_POWER: u16 = 0x15;
     const STATE_POWER: u16 = 0x16;
     const GET_LABEL: u16 = 0x17;
     const SET_LABEL: u16 = 0x18;
     const STATE_LABEL: u16 = 0x19;
+    const GET_VERSION: u16 = 0x20;
+    const STATE_VERSION: u16 = 0x21;
+    const GET_INFO: u16 = 0x22;
+    const STATE_INFO: u16 = 0x23;
     const GET_COLOR: u16 = 0x65;
     const SET_COLOR: u16 = 0x66;
+    const SET_WAVEFORM: u16 = 0x67;
     const LIGHT_STATE: u16 = 0x6B;
+    const GET_LIGHT_POWER: u16 = 0x74;
+    const SET_LIGHT_POWER: u16 = 0x75;
+    const STATE_LIGHT_POWER: u16 = 0x76;
+    const SET_WAVEFORM_OPTIONAL: u16 = 0x77;
+    const STATE_UNHANDLED: u16 = 0xDF;
 
+    /// Returns the protocol message type.
     pub fn ty(&self) -> u16 {
         use Message::*;
 
         match self {
             GetService => Self::GET_SERVICE,
+            StateService(..) => Self::STATE_SERVICE,
+            GetHostFirmware => Self::GET_HOST_FIRMWARE,
+            StateHostFirmware(_) => Self::STATE_HOST_FIRMWARE,
+            GetWifiInfo => Self::GET_WIFI_INFO,
+            StateWifiInfo(_) => Self::STATE_WIFI_INFO,
+            GetLocation => Self::GET_LOCATION,
+            SetLocation(_) => Self::SET_LOCATION,
+            StateLocation(_) => Self::STATE_LOCATION,
+            GetGroup => Self::GET_GROUP,
+            SetGroup(_) => Self::SET_GROUP,
+            StateGroup(_) => Self::STATE_GROUP,
+            GetVersion => Self::GET_VERSION,
+            StateVersion(_) => Self::STATE_VERSION,
+            GetInfo => Self::GET_INFO,
+            StateInfo(_) => Self::STATE_INFO,
+            Acknowledgement => Self::ACKNOWLEDGEMENT,
+            StateUnhandled(_) => Self::STATE_UNHANDLED,
             GetPower => Self::GET_POWER,
             SetPower(_) => Self::SET_POWER,
             StatePower(_) => Self::STATE_POWER,
@@ -68,20 +201,32 @@ impl Message {
             GetColor => Self::GET_COLOR,
             SetColor(..) => Self::SET_COLOR,
             LightState(..) => Self::LIGHT_STATE,
+            SetWaveform(_) => Self::SET_WAVEFORM,
+            SetWaveformOptional(..) => Self::SET_WAVEFORM_OPTIONAL,
+            GetLightPower => Self::GET_LIGHT_POWER,
+            SetLightPower(..) => Self::SET_LIGHT_POWER,
+            StateLightPower(_) => Self::STATE_LIGHT_POWER,
             Unknown => u16::MAX,
         }
     }
 
-    pub fn encode(&self, require_ack: bool, sequence: u8, target: Option<u64>) -> Vec<u8> {
+    /// Encodes the message into a complete packet.
+    pub fn encode(
+        &self,
+        source: u32,
+        require_ack: bool,
+        sequence: u8,
+        target: Option<Serial>,
+    ) -> Vec<u8> {
         let mut packet = Vec::new();
 
         // header
         packet.extend([0u8; 3]); // Reserve space for length + LSB of protocol
-        packet.push(0x14 | (target.is_some() as u8) << 5); // MSB of protocol and tagged bit
-        packet.extend(2u32.to_le_bytes()); // Source
+        packet.push(0x14 | (target.is_none() as u8) << 5); // MSB of protocol and tagged bit
+        packet.extend(source.to_le_bytes()); // Source
 
         // address
-        packet.extend(target.unwrap_or(0).to_le_bytes()); // Target
+        packet.extend(target.map_or(0, |t| t.0).to_le_bytes()); // Target
         packet.extend([0u8; 6]); // Reserved
         packet.push((require_ack as u8) << 1);
         packet.push(sequence); // Sequence
@@ -102,34 +247,84 @@ impl Message {
         packet
     }
 
+    /// Decodes a message payload of the given type.
     pub fn decode(ty: u16, payload: &[u8]) -> Result<Message> {
         Ok(match ty {
+            Self::STATE_SERVICE => {
+                ensure(payload.len() == 5)?;
+                let service = payload[0];
+                let port = read_u32(&payload[1..]);
+                Message::StateService(service, port)
+            }
+            Self::STATE_HOST_FIRMWARE => {
+                ensure(payload.len() == 20)?;
+                Message::StateHostFirmware(HostFirmware {
+                    build: read_u64(payload),
+                    version_minor: read_u16(&payload[16..]),
+                    version_major: read_u16(&payload[18..]),
+                })
+            }
+            Self::STATE_WIFI_INFO => {
+                ensure(payload.len() == 14)?;
+                let signal = f32::from_bits(read_u32(payload));
+                Message::StateWifiInfo(WifiInfo { signal })
+            }
+            Self::STATE_VERSION => {
+                ensure(payload.len() == 12)?;
+                Message::StateVersion(Version {
+                    vendor: read_u32(payload),
+                    product: read_u32(&payload[4..]),
+                })
+            }
+            Self::STATE_INFO => {
+                ensure(payload.len() == 24)?;
+                Message::StateInfo(DeviceInfo {
+                    time: read_u64(payload),
+                    uptime: read_u64(&payload[8..]),
+                    downtime: read_u64(&payload[16..]),
+                })
+            }
+            Self::ACKNOWLEDGEMENT => Message::Acknowledgement,
+            Self::STATE_LOCATION => {
+                ensure(payload.len() == 56)?;
+                Message::StateLocation(read_collection(payload)?)
+            }
+            Self::STATE_GROUP => {
+                ensure(payload.len() == 56)?;
+                Message::StateGroup(read_collection(payload)?)
+            }
+            Self::STATE_UNHANDLED => {
+                ensure(payload.len() == 2)?;
+                Message::StateUnhandled(read_u16(payload))
+            }
             Self::STATE_POWER => {
-                ensure!(payload.len() == 2, PACKET_ERROR);
+                ensure(payload.len() == 2)?;
                 let power = read_u16(payload);
                 Message::StatePower(power > 0)
             }
             Self::STATE_LABEL => {
-                ensure!(payload.len() == 32, PACKET_ERROR);
+                ensure(payload.len() == 32)?;
                 let label = read_lifx_str(payload)?;
                 Message::StateLabel(label.to_string())
             }
             Self::LIGHT_STATE => {
-                ensure!(payload.len() == 52, PACKET_ERROR);
+                ensure(payload.len() == 52)?;
                 let hue = read_u16(payload);
                 let saturation = read_u16(&payload[2..]);
                 let brightness = read_u16(&payload[4..]);
                 let kelvin = read_u16(&payload[6..]);
                 let power = read_u16(&payload[10..]);
                 let label = read_lifx_str(&payload[12..])?;
-                Message::LightState(
-                    hue,
-                    saturation,
-                    brightness,
-                    kelvin,
-                    power > 0,
-                    label.to_string(),
-                )
+                Message::LightState(crate::LightState {
+                    color: Hsbk::from_raw(hue, saturation, brightness, kelvin),
+                    power: power > 0,
+                    label: label.to_string(),
+                })
+            }
+            Self::STATE_LIGHT_POWER => {
+                ensure(payload.len() == 2)?;
+                let power = read_u16(payload);
+                Message::StateLightPower(power > 0)
             }
             _ => Self::Unknown,
         })
@@ -143,12 +338,23 @@ impl Message {
                 let level = if *power { u16::MAX } else { 0 };
                 level.to_le_bytes().to_vec()
             }
-            SetLabel(label) => {
-                let mut chars = label.as_bytes().to_vec();
-                chars.resize(32, 0);
-                chars
+            SetLightPower(power, duration) => {
+                let level = if *power { u16::MAX } else { 0 };
+                let mut payload = Vec::with_capacity(6);
+                payload.extend(level.to_le_bytes());
+                payload.extend(duration.to_le_bytes());
+                payload
+            }
+            SetLabel(label) => write_lifx_str(label),
+            SetLocation(collection) | SetGroup(collection) => {
+                let mut payload = Vec::with_capacity(56);
+                payload.extend(collection.id);
+                payload.extend(write_lifx_str(&collection.label));
+                payload.extend(collection.updated_at.to_le_bytes());
+                payload
             }
-            SetColor(hue, saturation, brightness, kelvin, duration) => {
+            SetColor(color, duration) => {
+                let (hue, saturation, brightness, kelvin) = color.to_raw();
                 let mut payload = Vec::with_capacity(13);
                 payload.push(0);
                 payload.extend(hue.to_le_bytes());
@@ -158,30 +364,62 @@ impl Message {
                 payload.extend(duration.to_le_bytes());
                 payload
             }
+            SetWaveform(effect) => encode_waveform(effect),
+            SetWaveformOptional(effect, mask) => {
+                let mut payload = encode_waveform(effect);
+                payload.push(mask.hue as u8);
+                payload.push(mask.saturation as u8);
+                payload.push(mask.brightness as u8);
+                payload.push(mask.kelvin as u8);
+                payload
+            }
             _ => Vec::new(),
         }
     }
 }
 
+fn encode_waveform(effect: &WaveformEffect) -> Vec<u8> {
+    let (hue, saturation, brightness, kelvin) = effect.color.to_raw();
+    let mut payload = Vec::with_capacity(25);
+    payload.push(0);
+    payload.push(effect.transient as u8);
+    payload.extend(hue.to_le_bytes());
+    payload.extend(saturation.to_le_bytes());
+    payload.extend(brightness.to_le_bytes());
+    payload.extend(kelvin.to_le_bytes());
+    payload.extend(effect.period.to_le_bytes());
+    payload.extend(effect.cycles.to_le_bytes());
+    payload.extend(effect.raw_skew_ratio().to_le_bytes());
+    payload.push(effect.waveform.to_raw());
+    payload
+}
+
+/// A packet received from a device.
 pub struct Response {
+    /// Protocol message type.
     pub message_type: u16,
+    /// Raw message payload.
     pub payload: Vec<u8>,
+    /// Source identifier of the request being replied to.
     pub source: u32,
+    /// Serial of the replying device, in its protocol representation.
     pub target: u64,
+    /// Sequence number of the request being replied to.
     pub sequence: u8,
-
-    pub message: Option<Message>,
+    /// Decoded message, which is [`Message::Unknown`] for unsupported types.
+    pub message: Message,
 }
 
 impl Response {
+    /// Decodes a raw packet.
     pub fn decode(raw: &[u8]) -> Result<Response> {
         // Read packet length
-        ensure!(raw.len() > 2, PACKET_ERROR);
+        ensure(raw.len() >= 36)?;
         let length = read_u16(raw);
-        ensure!(raw.len() < length as usize, PACKET_ERROR);
+        ensure(length >= 36 && raw.len() >= length as usize)?;
 
         // Check protocol
-        ensure!(raw[2] == 0 && (raw[3] & !0xF8) == 4, PACKET_ERROR);
+        ensure(raw[2] == 0 && (raw[3] & !0xF8) == 4)?;
 
         let source = read_u32(&raw[4..]);
         let target = read_u64(&raw[8..]);
@@ -197,7 +435,367 @@ impl Response {
             source,
             target,
             sequence,
-            message: Some(message),
+            message,
         })
     }
 }
+
+/// Serial number of a device, which is also its MAC address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Serial(pub u64);
+
+impl std::fmt::Display for Serial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for b in &self.0.to_le_bytes()[..6] {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Serial {
+    type Err = LifxError;
+
+    /// Parses a serial written as 12 hexadecimal digits, optionally separated by colons.
+    fn from_str(s: &str) -> Result<Serial> {
+        let digits: String = s.chars().filter(|c| *c != ':').collect();
+        if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(LifxError::InvalidSerial(s.to_string()));
+        }
+
+        let mut bytes = [0u8; 8];
+        for (i, byte) in bytes[..6].iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+                .map_err(|_| LifxError::InvalidSerial(s.to_string()))?;
+        }
+        Ok(Serial(u64::from_le_bytes(bytes)))
+    }
+}
+
+impl serde::Serialize for Serial {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> std::result::Result<S::Ok, S::Error> {
+        ser.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Serial {
+    fn deserialize<D: serde::Deserializer<'de>>(de: D) -> std::result::Result<Serial, D::Error> {
+        let raw = String::deserialize(de)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::waveform::tests::effect;
+
+    const HEADER_LEN: usize = 36;
+
+    /// Encodes a message, checking the header and returning the payload.
+    fn encode_payload(message: Message) -> Vec<u8> {
+        let packet = message.encode(2, false, 7, None);
+        assert_eq!(read_u16(&packet) as usize, packet.len());
+        assert_eq!(read_u16(&packet[32..]), message.ty());
+        packet[HEADER_LEN..].to_vec()
+    }
+
+    /// Builds a packet as sent by a device.
+    fn device_packet(ty: u16, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0u8; HEADER_LEN];
+        packet[..2].copy_from_slice(&((HEADER_LEN + payload.len()) as u16).to_le_bytes());
+        packet[3] = 0x14;
+        packet[4..8].copy_from_slice(&0xdeadbeefu32.to_le_bytes());
+        packet[8..16].copy_from_slice(&0x0000_ccbb_aad5_73d0u64.to_le_bytes());
+        packet[23] = 42;
+        packet[32..34].copy_from_slice(&ty.to_le_bytes());
+        packet.extend(payload);
+        packet
+    }
+
+    #[test]
+    fn decode_state_service() {
+        let mut payload = vec![1];
+        payload.extend(56700u32.to_le_bytes());
+
+        let message = Message::decode(Message::STATE_SERVICE, &payload).unwrap();
+        assert!(matches!(message, Message::StateService(1, 56700)));
+        assert!(Message::decode(Message::STATE_SERVICE, &payload[..4]).is_err());
+    }
+
+    #[test]
+    fn decode_state_host_firmware() {
+        let mut payload = vec![0u8; 20];
+        payload[..8].copy_from_slice(&1_500_000_000u64.to_le_bytes());
+        payload[16..18].copy_from_slice(&70u16.to_le_bytes());
+        payload[18..20].copy_from_slice(&3u16.to_le_bytes());
+
+        let Message::StateHostFirmware(firmware) =
+            Message::decode(Message::STATE_HOST_FIRMWARE, &payload).unwrap()
+        else {
+            panic!("expected StateHostFirmware");
+        };
+        assert_eq!(
+            firmware,
+            HostFirmware {
+                build: 1_500_000_000,
+                version_major: 3,
+                version_minor: 70,
+            }
+        );
+        assert!(Message::decode(Message::STATE_HOST_FIRMWARE, &payload[..19]).is_err());
+    }
+
+    #[test]
+    fn decode_state_wifi_info() {
+        let mut payload = vec![0u8; 14];
+        payload[..4].copy_from_slice(&1e-5f32.to_le_bytes());
+
+        let Message::StateWifiInfo(info) =
+            Message::decode(Message::STATE_WIFI_INFO, &payload).unwrap()
+        else {
+            panic!("expected StateWifiInfo");
+        };
+        assert_eq!(info.signal, 1e-5);
+        assert!(Message::decode(Message::STATE_WIFI_INFO, &payload[..4]).is_err());
+    }
+
+    #[test]
+    fn decode_state_version() {
+        let mut payload = vec![0u8; 12];
+        payload[..4].copy_from_slice(&1u32.to_le_bytes());
+        payload[4..8].copy_from_slice(&27u32.to_le_bytes());
+
+        let Message::StateVersion(version) =
+            Message::decode(Message::STATE_VERSION, &payload).unwrap()
+        else {
+            panic!("expected StateVersion");
+        };
+        assert_eq!(
+            version,
+            Version {
+                vendor: 1,
+                product: 27,
+            }
+        );
+        assert!(Message::decode(Message::STATE_VERSION, &payload[..8]).is_err());
+    }
+
+    #[test]
+    fn decode_state_info() {
+        let mut payload = Vec::new();
+        payload.extend(1_600_000_000_000_000_000u64.to_le_bytes());
+        payload.extend(3_600_000_000_000u64.to_le_bytes());
+        payload.extend(60_000_000_000u64.to_le_bytes());
+
+        let Message::StateInfo(info) = Message::decode(Message::STATE_INFO, &payload).unwrap()
+        else {
+            panic!("expected StateInfo");
+        };
+        assert_eq!(
+            info,
+            DeviceInfo {
+                time: 1_600_000_000_000_000_000,
+                uptime: 3_600_000_000_000,
+                downtime: 60_000_000_000,
+            }
+        );
+        assert!(Message::decode(Message::STATE_INFO, &payload[..23]).is_err());
+    }
+
+    #[test]
+    fn decode_acknowledgement() {
+        let message = Message::decode(Message::ACKNOWLEDGEMENT, &[]).unwrap();
+        assert!(matches!(message, Message::Acknowledgement));
+    }
+
+    #[test]
+    fn decode_state_unhandled() {
+        let payload = Message::SET_WAVEFORM.to_le_bytes();
+
+        let message = Message::decode(Message::STATE_UNHANDLED, &payload).unwrap();
+        assert!(matches!(
+            message,
+            Message::StateUnhandled(Message::SET_WAVEFORM)
+        ));
+        assert!(Message::decode(Message::STATE_UNHANDLED, &payload[..1]).is_err());
+    }
+
+    #[test]
+    fn encode_set_light_power() {
+        let payload = encode_payload(Message::SetLightPower(true, 1500));
+        assert_eq!(payload, [0xff, 0xff, 0xdc, 0x05, 0x00, 0x00]);
+
+        let payload = encode_payload(Message::SetLightPower(false, 0));
+        assert_eq!(payload, [0; 6]);
+    }
+
+    #[test]
+    fn decode_state_light_power() {
+        let message = Message::decode(Message::STATE_LIGHT_POWER, &[0xff, 0xff]).unwrap();
+        assert!(matches!(message, Message::StateLightPower(true)));
+
+        let message = Message::decode(Message::STATE_LIGHT_POWER, &[0, 0]).unwrap();
+        assert!(matches!(message, Message::StateLightPower(false)));
+
+        assert!(Message::decode(Message::STATE_LIGHT_POWER, &[0xff, 0xff, 0]).is_err());
+    }
+
+    fn collection() -> Collection {
+        Collection {
+            id: [7; 16],
+            label: "Upstairs".to_string(),
+            updated_at: 1_600_000_000_000_000_000,
+        }
+    }
+
+    fn collection_payload() -> Vec<u8> {
+        let mut payload = vec![7; 16];
+        payload.extend(b"Upstairs");
+        payload.extend([0; 24]);
+        payload.extend(1_600_000_000_000_000_000u64.to_le_bytes());
+        payload
+    }
+
+    #[test]
+    fn encode_set_collection() {
+        let payload = encode_payload(Message::SetGroup(collection()));
+        assert_eq!(payload, collection_payload());
+
+        let payload = encode_payload(Message::SetLocation(collection()));
+        assert_eq!(payload, collection_payload());
+    }
+
+    #[test]
+    fn decode_state_collection() {
+        let payload = collection_payload();
+
+        let Message::StateGroup(group) = Message::decode(Message::STATE_GROUP, &payload).unwrap()
+        else {
+            panic!("expected StateGroup");
+        };
+        assert_eq!(group, collection());
+
+        let Message::StateLocation(location) =
+            Message::decode(Message::STATE_LOCATION, &payload).unwrap()
+        else {
+            panic!("expected StateLocation");
+        };
+        assert_eq!(location, collection());
+
+        assert!(Message::decode(Message::STATE_GROUP, &payload[..55]).is_err());
+        assert!(Message::decode(Message::STATE_LOCATION, &payload[..55]).is_err());
+    }
+
+    #[test]
+    fn decode_response() {
+        let packet = device_packet(Message::STATE_POWER, &[0xff, 0xff]);
+        let response = Response::decode(&packet).unwrap();
+
+        assert_eq!(response.message_type, Message::STATE_POWER);
+        assert_eq!(response.source, 0xdeadbeef);
+        assert_eq!(response.target, 0x0000_ccbb_aad5_73d0);
+        assert_eq!(response.sequence, 42);
+        assert_eq!(response.payload, [0xff, 0xff]);
+        assert!(matches!(response.message, Message::StatePower(true)));
+    }
+
+    #[test]
+    fn decode_response_length() {
+        let packet = device_packet(Message::STATE_POWER, &[0xff, 0xff]);
+
+        // Bytes beyond the length prefix are ignored
+        let mut padded = packet.clone();
+        padded.extend([0; 4]);
+        assert_eq!(Response::decode(&padded).unwrap().payload, [0xff, 0xff]);
+
+        // Truncated packets and lengths shorter than the header are rejected
+        assert!(Response::decode(&packet[..packet.len() - 1]).is_err());
+        assert!(Response::decode(&packet[..HEADER_LEN - 1]).is_err());
+        let mut short = packet.clone();
+        short[..2].copy_from_slice(&20u16.to_le_bytes());
+        assert!(Response::decode(&short).is_err());
+
+        // Payloads of the wrong size for their type are malformed
+        assert!(Response::decode(&device_packet(Message::STATE_POWER, &[0xff])).is_err());
+    }
+
+    #[test]
+    fn encode_set_waveform() {
+        let payload = encode_payload(Message::SetWaveform(effect(1.0)));
+
+        let mut expected = vec![0, 1];
+        expected.extend([0x34, 0x12, 0x78, 0x56, 0xbc, 0x9a]);
+        expected.extend(3500u16.to_le_bytes());
+        expected.extend(2000u32.to_le_bytes());
+        expected.extend(2.5f32.to_le_bytes());
+        expected.extend([0xff, 0x7f]);
+        expected.push(4);
+        assert_eq!(payload.len(), 21);
+        assert_eq!(payload, expected);
+    }
+
+    #[test]
+    fn encode_set_waveform_optional() {
+        let mask = HsbkMask {
+            hue: true,
+            saturation: false,
+            brightness: true,
+            kelvin: false,
+        };
+        let payload = encode_payload(Message::SetWaveformOptional(effect(1.0), mask));
+
+        assert_eq!(payload.len(), 25);
+        assert_eq!(
+            payload[..21],
+            encode_payload(Message::SetWaveform(effect(1.0)))[..]
+        );
+        assert_eq!(payload[21..], [1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn serial_round_trip() {
+        let serial = Serial(0x0000_ccbb_aad5_73d0);
+        assert_eq!(serial.to_string(), "d073d5aabbcc");
+        assert_eq!("d073d5aabbcc".parse::<Serial>().unwrap(), serial);
+        assert_eq!("D0:73:D5:AA:BB:CC".parse::<Serial>().unwrap(), serial);
+        assert_eq!(serial.to_string().parse::<Serial>().unwrap(), serial);
+    }
+
+    #[test]
+    fn serial_rejects_invalid() {
+        for raw in [
+            "",
+            "d073d5aabb",
+            "d073d5aabbccdd",
+            "d0:73:d5:aa:bb",
+            "d073d5aabbcg",
+            "+073d5aabbcc",
+            "d073d5aabbé",
+        ] {
+            assert!(
+                matches!(raw.parse::<Serial>(), Err(LifxError::InvalidSerial(_))),
+                "{:?} should be rejected",
+                raw
+            );
+        }
+    }
+
+    #[test]
+    fn encode_header() {
+        let broadcast = Message::GetPower.encode(0xdeadbeef, true, 42, None);
+        assert_eq!(broadcast.len(), HEADER_LEN);
+        assert_eq!(broadcast[2..4], [0x00, 0x34]);
+        assert_eq!(broadcast[4..8], 0xdeadbeefu32.to_le_bytes());
+        assert_eq!(broadcast[8..16], [0; 8]);
+        assert_eq!(broadcast[22], 0x02);
+        assert_eq!(broadcast[23], 42);
+
+        // Addressed packets clear the tagged bit
+        let serial = Serial(0x0000_ccbb_aad5_73d0);
+        let addressed = Message::GetPower.encode(0xdeadbeef, false, 42, Some(serial));
+        assert_eq!(addressed[2..4], [0x00, 0x14]);
+        assert_eq!(addressed[8..16], [0xd0, 0x73, 0xd5, 0xaa, 0xbb, 0xcc, 0, 0]);
+        assert_eq!(addressed[22], 0x00);
+    }
+}
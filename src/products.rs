@@ -0,0 +1,344 @@
+use crate::HostFirmware;
+
+/// Capabilities of a LIFX product.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Features {
+    /// Can display colors rather than only white.
+    pub color: bool,
+    /// Has infrared LEDs for night vision.
+    pub infrared: bool,
+    /// Has individually addressable zones along a strip.
+    pub multizone: bool,
+    /// Supports setting every zone with a single message.
+    pub extended_multizone: bool,
+    /// Has a two dimensional grid of zones.
+    pub matrix: bool,
+    /// Can be chained to other devices.
+    pub chain: bool,
+    /// Has HEV cleaning LEDs.
+    pub hev: bool,
+    /// Has relays for switching loads.
+    pub relays: bool,
+    /// Has physical buttons.
+    pub buttons: bool,
+    /// Supported color temperatures (in kelvin), if the product is a light.
+    pub temperature_range: Option<(u16, u16)>,
+}
+
+/// Features gained by a product from a firmware version onwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Upgrade {
+    /// Major version number of the firmware.
+    pub version_major: u16,
+    /// Minor version number of the firmware.
+    pub version_minor: u16,
+    /// Whether extended multizone messages become supported.
+    pub extended_multizone: bool,
+    /// New range of supported color temperatures (in kelvin), if it changes.
+    pub temperature_range: Option<(u16, u16)>,
+}
+
+impl Upgrade {
+    /// Returns whether the upgrade is included in the given firmware.
+    pub fn applies_to(&self, firmware: &HostFirmware) -> bool {
+        (firmware.version_major, firmware.version_minor) >= (self.version_major, self.version_minor)
+    }
+}
+
+/// A LIFX product, identified by its vendor and product ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Product {
+    /// Vendor ID.
+    pub vendor: u32,
+    /// Product ID within the vendor.
+    pub product: u32,
+    /// Marketing name of the product.
+    pub name: &'static str,
+    /// Capabilities of the product with its original firmware.
+    pub features: Features,
+    /// Capabilities added by later firmware, in version order.
+    pub upgrades: &'static [Upgrade],
+}
+
+impl Product {
+    const fn new(product: u32, name: &'static str, features: Features) -> Product {
+        Product {
+            vendor: LIFX_VENDOR,
+            product,
+            name,
+            features,
+            upgrades: &[],
+        }
+    }
+
+    const fn with_upgrades(self, upgrades: &'static [Upgrade]) -> Product {
+        Product { upgrades, ..self }
+    }
+
+    /// Returns the capabilities of the product when running the given firmware.
+    pub fn features_for(&self, firmware: &HostFirmware) -> Features {
+        let mut features = self.features;
+        for upgrade in self.upgrades.iter().filter(|u| u.applies_to(firmware)) {
+            features.extended_multizone |= upgrade.extended_multizone;
+            if let Some(range) = upgrade.temperature_range {
+                features.temperature_range = Some(range);
+            }
+        }
+        features
+    }
+
+    /// Finds a product in the built-in registry.
+    pub fn lookup(vendor: u32, product: u32) -> Option<&'static Product> {
+        PRODUCTS
+            .iter()
+            .find(|p| p.vendor == vendor && p.product == product)
+    }
+
+    /// Returns every product in the built-in registry.
+    pub fn all() -> &'static [Product] {
+        PRODUCTS
+    }
+}
+
+/// Vendor ID of LIFX.
+pub const LIFX_VENDOR: u32 = 1;
+
+const NONE: Features = Features {
+    color: false,
+    infrared: false,
+    multizone: false,
+    extended_multizone: false,
+    matrix: false,
+    chain: false,
+    hev: false,
+    relays: false,
+    buttons: false,
+    temperature_range: None,
+};
+
+const COLOR: Features = Features {
+    color: true,
+    temperature_range: Some((2500, 9000)),
+    ..NONE
+};
+
+const COLOR_WIDE: Features = Features {
+    temperature_range: Some((1500, 9000)),
+    ..COLOR
+};
+
+const INFRARED: Features = Features {
+    infrared: true,
+    ..COLOR
+};
+
+const INFRARED_WIDE: Features = Features {
+    infrared: true,
+    ..COLOR_WIDE
+};
+
+const MULTIZONE: Features = Features {
+    multizone: true,
+    ..COLOR
+};
+
+const EXTENDED_MULTIZONE_WIDE: Features = Features {
+    multizone: true,
+    extended_multizone: true,
+    ..COLOR_WIDE
+};
+
+const TILE: Features = Features {
+    matrix: true,
+    chain: true,
+    ..COLOR
+};
+
+const MATRIX: Features = Features {
+    matrix: true,
+    ..COLOR_WIDE
+};
+
+const HEV: Features = Features {
+    hev: true,
+    ..COLOR_WIDE
+};
+
+const SWITCH: Features = Features {
+    relays: true,
+    buttons: true,
+    ..NONE
+};
+
+const WIDE_TEMPERATURE: Upgrade = Upgrade {
+    version_major: 2,
+    version_minor: 80,
+    extended_multizone: false,
+    temperature_range: Some((1500, 9000)),
+};
+
+const WIDE_TEMPERATURE_UPGRADES: &[Upgrade] = &[WIDE_TEMPERATURE];
+
+const MULTIZONE_UPGRADES: &[Upgrade] = &[
+    Upgrade {
+        version_major: 2,
+        version_minor: 77,
+        extended_multizone: true,
+        temperature_range: None,
+    },
+    WIDE_TEMPERATURE,
+];
+
+const fn white(min: u16, max: u16) -> Features {
+    Features {
+        temperature_range: Some((min, max)),
+        ..NONE
+    }
+}
+
+// Derived from the public LIFX products.json
+static PRODUCTS: &[Product] = &[
+    Product::new(1, "LIFX Original 1000", COLOR),
+    Product::new(3, "LIFX Color 650", COLOR),
+    Product::new(10, "LIFX White 800 (Low Voltage)", white(2700, 6500)),
+    Product::new(11, "LIFX White 800 (High Voltage)", white(2700, 6500)),
+    Product::new(15, "LIFX Color 1000", COLOR),
+    Product::new(18, "LIFX White 900 BR30 (Low Voltage)", white(2500, 9000)),
+    Product::new(19, "LIFX White 900 BR30 (High Voltage)", white(2500, 9000)),
+    Product::new(20, "LIFX Color 1000 BR30", COLOR),
+    Product::new(22, "LIFX Color 1000", COLOR),
+    Product::new(27, "LIFX A19", COLOR).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(28, "LIFX BR30", COLOR).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(29, "LIFX A19 Night Vision", INFRARED).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(30, "LIFX BR30 Night Vision", INFRARED).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(31, "LIFX Z", MULTIZONE),
+    Product::new(32, "LIFX Z", MULTIZONE).with_upgrades(MULTIZONE_UPGRADES),
+    Product::new(36, "LIFX Downlight", COLOR).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(37, "LIFX Downlight", COLOR).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(38, "LIFX Beam", MULTIZONE).with_upgrades(MULTIZONE_UPGRADES),
+    Product::new(39, "LIFX Downlight White to Warm", white(1500, 9000)),
+    Product::new(40, "LIFX Downlight", COLOR),
+    Product::new(43, "LIFX A19", COLOR).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(44, "LIFX BR30", COLOR).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(45, "LIFX A19 Night Vision", INFRARED).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(46, "LIFX BR30 Night Vision", INFRARED).with_upgrades(WIDE_TEMPERATURE_UPGRADES),
+    Product::new(49, "LIFX Mini Color", COLOR_WIDE),
+    Product::new(50, "LIFX Mini White to Warm", white(1500, 4000)),
+    Product::new(51, "LIFX Mini White", white(2700, 2700)),
+    Product::new(52, "LIFX GU10", COLOR_WIDE),
+    Product::new(53, "LIFX GU10", COLOR_WIDE),
+    Product::new(55, "LIFX Tile", TILE),
+    Product::new(57, "LIFX Candle", MATRIX),
+    Product::new(59, "LIFX Mini Color", COLOR_WIDE),
+    Product::new(60, "LIFX Mini White to Warm", white(1500, 4000)),
+    Product::new(61, "LIFX Mini White", white(2700, 2700)),
+    Product::new(62, "LIFX A19", COLOR_WIDE),
+    Product::new(63, "LIFX BR30", COLOR_WIDE),
+    Product::new(64, "LIFX A19 Night Vision", INFRARED_WIDE),
+    Product::new(65, "LIFX BR30 Night Vision", INFRARED_WIDE),
+    Product::new(66, "LIFX Mini White", white(2700, 2700)),
+    Product::new(68, "LIFX Candle", MATRIX),
+    Product::new(70, "LIFX Switch", SWITCH),
+    Product::new(71, "LIFX Switch", SWITCH),
+    Product::new(81, "LIFX Candle White to Warm", white(2200, 6500)),
+    Product::new(82, "LIFX Filament Clear", white(2100, 2100)),
+    Product::new(85, "LIFX Filament Amber", white(2000, 2000)),
+    Product::new(87, "LIFX Mini White", white(2700, 2700)),
+    Product::new(88, "LIFX Mini White", white(2700, 2700)),
+    Product::new(89, "LIFX Switch", SWITCH),
+    Product::new(90, "LIFX Clean", HEV),
+    Product::new(91, "LIFX Color", COLOR_WIDE),
+    Product::new(92, "LIFX Color", COLOR_WIDE),
+    Product::new(93, "LIFX A19 US", COLOR_WIDE),
+    Product::new(94, "LIFX BR30", COLOR_WIDE),
+    Product::new(96, "LIFX Candle White to Warm", white(2200, 6500)),
+    Product::new(97, "LIFX A19", COLOR_WIDE),
+    Product::new(98, "LIFX BR30", COLOR_WIDE),
+    Product::new(99, "LIFX Clean", HEV),
+    Product::new(100, "LIFX Filament Clear", white(2100, 2100)),
+    Product::new(101, "LIFX Filament Amber", white(2000, 2000)),
+    Product::new(109, "LIFX A19 Night Vision", INFRARED_WIDE),
+    Product::new(110, "LIFX BR30 Night Vision", INFRARED_WIDE),
+    Product::new(111, "LIFX A19 Night Vision", INFRARED_WIDE),
+    Product::new(112, "LIFX BR30 Night Vision Intl", INFRARED_WIDE),
+    Product::new(113, "LIFX Mini WW US", white(1500, 9000)),
+    Product::new(114, "LIFX Mini WW Intl", white(1500, 9000)),
+    Product::new(117, "LIFX Z US", EXTENDED_MULTIZONE_WIDE),
+    Product::new(118, "LIFX Z Intl", EXTENDED_MULTIZONE_WIDE),
+    Product::new(119, "LIFX Beam US", EXTENDED_MULTIZONE_WIDE),
+    Product::new(120, "LIFX Beam Intl", EXTENDED_MULTIZONE_WIDE),
+    Product::new(123, "LIFX Color US", COLOR_WIDE),
+    Product::new(124, "LIFX Color Intl", COLOR_WIDE),
+    Product::new(125, "LIFX White to Warm US", white(1500, 9000)),
+    Product::new(126, "LIFX White to Warm Intl", white(1500, 9000)),
+    Product::new(127, "LIFX White US", white(2700, 2700)),
+    Product::new(128, "LIFX White Intl", white(2700, 2700)),
+    Product::new(129, "LIFX Color US", COLOR_WIDE),
+    Product::new(130, "LIFX Color Intl", COLOR_WIDE),
+    Product::new(131, "LIFX White To Warm US", white(1500, 9000)),
+    Product::new(132, "LIFX White To Warm Intl", white(1500, 9000)),
+    Product::new(133, "LIFX White US", white(2700, 2700)),
+    Product::new(134, "LIFX White Intl", white(2700, 2700)),
+    Product::new(135, "LIFX GU10 Color US", COLOR_WIDE),
+    Product::new(136, "LIFX GU10 Color Intl", COLOR_WIDE),
+    Product::new(137, "LIFX Candle Color US", MATRIX),
+    Product::new(138, "LIFX Candle Color Intl", MATRIX),
+    Product::new(141, "LIFX Neon US", EXTENDED_MULTIZONE_WIDE),
+    Product::new(142, "LIFX Neon Intl", EXTENDED_MULTIZONE_WIDE),
+    Product::new(143, "LIFX String US", EXTENDED_MULTIZONE_WIDE),
+    Product::new(144, "LIFX String Intl", EXTENDED_MULTIZONE_WIDE),
+    Product::new(161, "LIFX Outdoor Neon US", EXTENDED_MULTIZONE_WIDE),
+    Product::new(162, "LIFX Outdoor Neon Intl", EXTENDED_MULTIZONE_WIDE),
+    Product::new(163, "LIFX A19 US", COLOR_WIDE),
+    Product::new(164, "LIFX BR30 US", COLOR_WIDE),
+    Product::new(165, "LIFX A19 Intl", COLOR_WIDE),
+    Product::new(166, "LIFX BR30 Intl", COLOR_WIDE),
+    Product::new(167, "LIFX Downlight", COLOR_WIDE),
+    Product::new(168, "LIFX Downlight", COLOR_WIDE),
+    Product::new(169, "LIFX A21 1600lm US", COLOR_WIDE),
+    Product::new(170, "LIFX A21 1600lm Intl", COLOR_WIDE),
+    Product::new(171, "LIFX Round Spot US", MATRIX),
+    Product::new(173, "LIFX Round Path US", MATRIX),
+    Product::new(174, "LIFX Square Path US", MATRIX),
+    Product::new(176, "LIFX Ceiling US", MATRIX),
+    Product::new(177, "LIFX Ceiling Intl", MATRIX),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn firmware(version_major: u16, version_minor: u16) -> HostFirmware {
+        HostFirmware {
+            build: 0,
+            version_major,
+            version_minor,
+        }
+    }
+
+    #[test]
+    fn upgrades_depend_on_firmware() {
+        let beam = Product::lookup(LIFX_VENDOR, 38).unwrap();
+
+        let features = beam.features_for(&firmware(2, 76));
+        assert!(features.multizone && !features.extended_multizone);
+        assert_eq!(features.temperature_range, Some((2500, 9000)));
+
+        let features = beam.features_for(&firmware(2, 77));
+        assert!(features.extended_multizone);
+        assert_eq!(features.temperature_range, Some((2500, 9000)));
+
+        let features = beam.features_for(&firmware(3, 0));
+        assert!(features.extended_multizone);
+        assert_eq!(features.temperature_range, Some((1500, 9000)));
+    }
+
+    #[test]
+    fn products_are_unique() {
+        for (i, product) in PRODUCTS.iter().enumerate() {
+            assert!(PRODUCTS[..i]
+                .iter()
+                .all(|p| (p.vendor, p.product) != (product.vendor, product.product)));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use crate::{Hsbk, LightConnection, Result, Serial};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+/// Color and power of a light, captured so that they can be restored later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    /// Whether the light was turned on.
+    pub power: bool,
+    /// Color of the light.
+    pub color: Hsbk,
+}
+
+impl StateSnapshot {
+    /// Captures the current color and power of a light.
+    pub async fn capture(conn: &mut LightConnection) -> Result<StateSnapshot> {
+        let color = conn.get_state().await?.color;
+        let power = conn.get_light_power().await?;
+        Ok(StateSnapshot { color, power })
+    }
+
+    /// Restores the captured color and power, transitioning over `duration`
+    /// milliseconds.
+    ///
+    /// The color is restored even if the light was captured while off, so that
+    /// it is correct when next turned on.
+    pub async fn restore(&self, conn: &mut LightConnection, duration: u32) -> Result<()> {
+        let power = conn.get_light_power().await?;
+
+        if self.power && !power {
+            // Fade in from the restored color rather than the current one
+            conn.set_color(self.color, 0).await?;
+            conn.set_light_power(true, duration).await?;
+        } else {
+            conn.set_color(self.color, duration).await?;
+            if self.power != power {
+                conn.set_light_power(self.power, duration).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The captured state of a single device.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Serial of the device, if known.
+    pub serial: Option<Serial>,
+    /// Address of the device when it was captured.
+    pub address: SocketAddr,
+    /// Captured color and power.
+    pub state: StateSnapshot,
+}
+
+/// The captured states of a set of devices.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NamedSnapshot {
+    /// Name to refer to the snapshot by.
+    pub name: String,
+    /// Time the snapshot was taken (in seconds since the epoch).
+    pub taken_at: u64,
+    /// State of each captured device.
+    pub devices: Vec<DeviceSnapshot>,
+}
+
+impl NamedSnapshot {
+    /// Creates a snapshot taken now.
+    pub fn new(name: &str, devices: Vec<DeviceSnapshot>) -> NamedSnapshot {
+        NamedSnapshot {
+            name: name.to_string(),
+            taken_at: crate::since_epoch().as_secs(),
+            devices,
+        }
+    }
+}
+
+/// Snapshots stored in the platform data directory.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct SnapshotStore {
+    #[serde(default)]
+    snapshots: Vec<NamedSnapshot>,
+}
+
+impl SnapshotStore {
+    /// Returns the location of the snapshot file.
+    pub fn path() -> Option<PathBuf> {
+        crate::project_dirs().map(|d| d.data_dir().join("snapshots.toml"))
+    }
+
+    /// Loads the snapshot file.
+    ///
+    /// A missing file results in no snapshots.
+    pub async fn load() -> Result<SnapshotStore> {
+        match Self::path() {
+            Some(p) => match fs::read_to_string(p).await {
+                Ok(raw) => Ok(toml::from_str(&raw)?),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+                Err(e) => Err(e.into()),
+            },
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Writes the snapshot file, creating its directory if needed.
+    pub async fn save(&self) -> Result<()> {
+        match Self::path() {
+            Some(path) => crate::write_file(&path, &toml::to_string(self)?).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the snapshot with the given name.
+    pub fn find(&self, name: &str) -> Option<&NamedSnapshot> {
+        self.snapshots.iter().find(|s| s.name == name)
+    }
+
+    /// Adds a snapshot, replacing any existing snapshot with the same name.
+    pub fn insert(&mut self, snapshot: NamedSnapshot) {
+        match self.snapshots.iter_mut().find(|s| s.name == snapshot.name) {
+            Some(existing) => *existing = snapshot,
+            None => self.snapshots.push(snapshot),
+        }
+    }
+}
@@ -1,32 +1,31 @@
-use anyhow::{anyhow, Result};
+use crate::Result;
+use directories::ProjectDirs;
 use std::{
+    collections::hash_map::RandomState,
     future::Future,
+    hash::{BuildHasher, Hasher},
     net::{IpAddr, SocketAddr},
-    time::Duration,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
+use tokio::{fs, io::AsyncWriteExt};
 
-pub enum Timeout<T> {
+/// Outcome of [`timeout`].
+pub(crate) enum Timeout<T> {
     Resolved(T),
     TimedOut,
 }
 
-impl<T> Timeout<T> {
-    pub fn expect_resolved(self) -> Result<T> {
-        match self {
-            Self::Resolved(v) => Ok(v),
-            Self::TimedOut => Err(anyhow!("Operation timed out.")),
-        }
-    }
-}
-
-pub async fn timeout<F: Future>(fut: F, ms: u64) -> Timeout<F::Output> {
+/// Runs `fut` for at most `ms` milliseconds.
+pub(crate) async fn timeout<F: Future>(fut: F, ms: u64) -> Timeout<F::Output> {
     match tokio::time::timeout(Duration::from_millis(ms), fut).await {
         Ok(v) => Timeout::Resolved(v),
         Err(_) => Timeout::TimedOut,
     }
 }
 
-pub fn parse_address(raw: &str) -> Option<SocketAddr> {
+/// Parses an address with an optional port, defaulting to [`crate::LIFX_PORT`].
+pub(crate) fn parse_address(raw: &str) -> Option<SocketAddr> {
     match raw.parse::<SocketAddr>() {
         Ok(addr) => Some(addr),
         Err(_) => match raw.parse::<IpAddr>() {
@@ -35,3 +34,51 @@ pub fn parse_address(raw: &str) -> Option<SocketAddr> {
         },
     }
 }
+
+/// Returns the platform directories lifxc stores its files in.
+pub(crate) fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("com", "psr31", "lifxc")
+}
+
+/// Writes a file, creating its directory if needed.
+///
+/// The contents are written to a temporary file in the same directory, which
+/// then replaces the original so that it is never left partially written.
+pub(crate) async fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.{:016x}.tmp", name, random_u64()));
+    let result = async {
+        let mut file = fs::File::create(&temp).await?;
+        file.write_all(contents.as_bytes()).await?;
+        file.sync_all().await?;
+
+        // Keep the permissions of the file being replaced
+        if let Ok(metadata) = fs::metadata(path).await {
+            fs::set_permissions(&temp, metadata.permissions()).await?;
+        }
+        fs::rename(&temp, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp).await;
+    }
+    Ok(result?)
+}
+
+/// Returns the time elapsed since the epoch, or zero if the clock is before it.
+pub(crate) fn since_epoch() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Returns a random number.
+pub(crate) fn random_u64() -> u64 {
+    // RandomState is seeded randomly for each instance
+    RandomState::new().build_hasher().finish()
+}
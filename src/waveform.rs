@@ -0,0 +1,106 @@
+use crate::Hsbk;
+
+/// Shape of a waveform effect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Waveform {
+    /// Transitions linearly to the color, then jumps back.
+    Saw,
+    /// Transitions smoothly to the color and back.
+    Sine,
+    /// Transitions smoothly to the color, then jumps back.
+    HalfSine,
+    /// Transitions linearly to the color and back.
+    Triangle,
+    /// Switches between the colors, as set by the skew ratio.
+    Pulse,
+}
+
+impl Waveform {
+    /// Returns the protocol value of the waveform.
+    pub fn to_raw(self) -> u8 {
+        match self {
+            Waveform::Saw => 0,
+            Waveform::Sine => 1,
+            Waveform::HalfSine => 2,
+            Waveform::Triangle => 3,
+            Waveform::Pulse => 4,
+        }
+    }
+}
+
+/// A waveform effect transitioning a light towards a color and back.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WaveformEffect {
+    /// Shape of the effect.
+    pub waveform: Waveform,
+    /// Color to transition towards.
+    pub color: Hsbk,
+    /// Whether the light returns to its original color after the effect.
+    pub transient: bool,
+    /// Duration (in milliseconds) of a single cycle.
+    pub period: u32,
+    /// Number of cycles to perform.
+    pub cycles: f32,
+    /// Fraction of each cycle spent on the original color, from 0 to 1.
+    ///
+    /// Only used by the pulse waveform.
+    pub skew_ratio: f32,
+}
+
+impl WaveformEffect {
+    /// Returns the skew ratio in its protocol representation.
+    pub fn raw_skew_ratio(&self) -> i16 {
+        let ratio = if self.skew_ratio.is_nan() {
+            0.5
+        } else {
+            self.skew_ratio.clamp(0.0, 1.0)
+        };
+        // Rounded before offsetting so that an even ratio maps to zero
+        ((ratio * u16::MAX as f32).round() as i32 + i16::MIN as i32) as i16
+    }
+}
+
+/// Selects which components of a color a waveform effect applies to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HsbkMask {
+    /// Whether the hue is changed.
+    pub hue: bool,
+    /// Whether the saturation is changed.
+    pub saturation: bool,
+    /// Whether the brightness is changed.
+    pub brightness: bool,
+    /// Whether the kelvin is changed.
+    pub kelvin: bool,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A pulse effect with distinct bytes in each field, also used by the
+    /// packet encoding tests.
+    pub(crate) fn effect(skew_ratio: f32) -> WaveformEffect {
+        WaveformEffect {
+            waveform: Waveform::Pulse,
+            color: Hsbk::from_raw(0x1234, 0x5678, 0x9abc, 3500),
+            transient: true,
+            period: 2000,
+            cycles: 2.5,
+            skew_ratio,
+        }
+    }
+
+    #[test]
+    fn skew_ratio_spans_i16() {
+        assert_eq!(effect(0.0).raw_skew_ratio(), i16::MIN);
+        assert_eq!(effect(0.5).raw_skew_ratio(), 0);
+        assert_eq!(effect(1.0).raw_skew_ratio(), i16::MAX);
+    }
+
+    #[test]
+    fn skew_ratio_is_clamped() {
+        assert_eq!(effect(-1.0).raw_skew_ratio(), i16::MIN);
+        assert_eq!(effect(2.0).raw_skew_ratio(), i16::MAX);
+        assert_eq!(effect(f32::NAN).raw_skew_ratio(), 0);
+    }
+}